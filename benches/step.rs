@@ -0,0 +1,84 @@
+//! Times a loop-heavy program with and without fast dispatch, and with `run_fast`. `criterion`
+//! isn't a dependency so this is a plain binary: run it with `cargo bench --bench step`.
+
+use std::time::{Duration, Instant};
+
+use lilc3::{
+    instruction::{AddImmediate, AndImmediate, Branch, Instruction, Load, Trap, TrapCode},
+    io::BufferedIo,
+    CondFlag, LC3, MAX_MEMORY_SIZE, PROGRAM_START,
+};
+
+const RUNS: u32 = 20;
+
+fn machine() -> LC3<BufferedIo> {
+    let program = [
+        Instruction::Load(Load {
+            dr: 0,
+            pc_offset9: 5,
+        }),
+        Instruction::AddImmediate(AddImmediate {
+            dr: 1,
+            sr1: 1,
+            imm5: 1,
+        }),
+        Instruction::AndImmediate(AndImmediate {
+            dr: 2,
+            sr1: 1,
+            imm5: 0xF,
+        }),
+        Instruction::AddImmediate(AddImmediate {
+            dr: 0,
+            sr1: 0,
+            imm5: 0x1F, // negative one
+        }),
+        Instruction::Branch(Branch {
+            nzp: CondFlag::POSITIVE,
+            pc_offset9: 0xFFFC, // negative four
+        }),
+        Instruction::Trap(Trap {
+            vect8: TrapCode::Halt,
+        }),
+    ];
+
+    let mut words: Vec<u16> = program.iter().map(|i| u16::from_be(i.encode())).collect();
+    words.push(0x7FFF);
+
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(BufferedIo::default());
+    machine.load_words_at(PROGRAM_START, &words).unwrap();
+    machine
+}
+
+fn time(fast_dispatch: bool, run: fn(&mut LC3<BufferedIo>)) -> Duration {
+    let mut total = Duration::default();
+    for _ in 0..RUNS {
+        let mut machine = machine();
+        machine.fast_dispatch = fast_dispatch;
+
+        let start = Instant::now();
+        run(&mut machine);
+        total += start.elapsed();
+    }
+    total / RUNS
+}
+
+fn main() {
+    let decoded = time(false, |machine| {
+        machine.run();
+    });
+    let fast = time(true, |machine| {
+        machine.run();
+    });
+    let run_fast = time(true, |machine| {
+        machine.run_fast();
+    });
+
+    println!("decoded dispatch: {:?} per run", decoded);
+    println!("fast dispatch:    {:?} per run", fast);
+    println!("run_fast:         {:?} per run", run_fast);
+    println!(
+        "speedup:          {:.2}x fast dispatch, {:.2}x run_fast",
+        decoded.as_secs_f64() / fast.as_secs_f64(),
+        decoded.as_secs_f64() / run_fast.as_secs_f64()
+    );
+}
@@ -0,0 +1,74 @@
+use std::collections::HashSet;
+
+use super::{
+    instruction::{Instruction, TrapCode},
+    io::Io,
+    memory::MemoryBacking,
+    CondFlag, MemoryLocationSize, LC3,
+};
+
+impl<I: Io, M: MemoryBacking> LC3<I, M> {
+    /// Returns the addresses of every instruction that can be reached by following the program in
+    /// memory from `entry` without running it.
+    ///
+    /// The traversal follows fall-through, branch and JSR targets. Subroutines are assumed to
+    /// return to the instruction after the call. It stops at HALT, at JMP/RET since the target
+    /// isn't known statically and at words that don't decode. JSRR targets aren't followed for the
+    /// same reason.
+    pub fn reachable_from(&self, entry: MemoryLocationSize) -> HashSet<MemoryLocationSize> {
+        let mut reachable = HashSet::new();
+        let mut pending = vec![entry];
+        while let Some(address) = pending.pop() {
+            if !reachable.insert(address) {
+                continue;
+            }
+
+            let next = address.wrapping_add(1);
+            let instr = match Instruction::try_decode(self.memory.read(address), self.decode_mode) {
+                Ok(instr) => instr,
+                Err(_) => continue,
+            };
+            match instr {
+                Instruction::Branch(branch) => {
+                    if !branch.nzp.is_empty() {
+                        pending.push(next.wrapping_add(branch.pc_offset9));
+                    }
+                    // The condition codes always have a flag set so BRnzp always branches
+                    if branch.nzp != CondFlag::all() {
+                        pending.push(next);
+                    }
+                }
+                Instruction::JumpSubRoutineOffset(jsr) => {
+                    pending.push(next.wrapping_add(jsr.pc_offset11));
+                    pending.push(next);
+                }
+                Instruction::Jump(_) => {}
+                Instruction::Trap(trap) if trap.vect8 == TrapCode::Halt => {}
+                _ => pending.push(next),
+            }
+        }
+
+        reachable
+    }
+
+    /// Returns whether a HALT trap is reachable from `entry`, see `reachable_from`. A program
+    /// without one can only stop by running out of input or cycles.
+    pub fn has_reachable_halt(&self, entry: MemoryLocationSize) -> bool {
+        self.reachable_from(entry).into_iter().any(|address| {
+            match Instruction::try_decode(self.memory.read(address), self.decode_mode) {
+                Ok(Instruction::Trap(trap)) => trap.vect8 == TrapCode::Halt,
+                _ => false,
+            }
+        })
+    }
+
+    /// Returns the standard traps whose entry in the trap vector table is still 0, i.e. that
+    /// would jump to address 0 if dispatched through the table. The machine handles traps itself
+    /// so this only matters for programs that bring their own OS, see `load_additional`.
+    pub fn validate_trap_table(&self) -> Vec<TrapCode> {
+        (0..=u8::MAX)
+            .filter_map(TrapCode::try_from_bits)
+            .filter(|code| !code.is_extended() && self.memory.read(*code as u16) == 0)
+            .collect()
+    }
+}
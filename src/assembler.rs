@@ -0,0 +1,10 @@
+/// Returns the word `.FILL value` produces
+pub fn fill(value: i16) -> u16 {
+    value as u16
+}
+
+/// Returns the words `.STRINGZ "s"` produces: one word per byte of `s` followed by a null
+/// terminator
+pub fn stringz(s: &str) -> Vec<u16> {
+    s.bytes().map(u16::from).chain(Some(0)).collect()
+}
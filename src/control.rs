@@ -0,0 +1,64 @@
+use std::sync::{
+    atomic::{AtomicBool, AtomicU64, Ordering},
+    Arc,
+};
+
+use super::{io::Io, memory::MemoryBacking, LC3};
+
+/// A handle for controlling a machine that's running on another thread, see `LC3::run_control`.
+/// Clones share the same machine.
+///
+/// There's no separate pause: stopping leaves the machine as it was after its last instruction
+/// so calling `run` again resumes the program where it left off.
+#[derive(Debug, Clone, Default)]
+pub struct RunControl {
+    shared: Arc<Shared>,
+}
+
+#[derive(Debug, Default)]
+struct Shared {
+    stop_requested: AtomicBool,
+    cycles: AtomicU64,
+}
+
+impl RunControl {
+    /// Makes `run` return `StopReason::Stopped` after the instruction it's executing. If the
+    /// machine isn't running the next call to `run` returns straight away.
+    pub fn request_stop(&self) {
+        self.shared.stop_requested.store(true, Ordering::SeqCst);
+    }
+
+    /// Returns the number of instructions the machine had executed as of its last check of the
+    /// handle
+    pub fn cycles(&self) -> u64 {
+        self.shared.cycles.load(Ordering::Relaxed)
+    }
+
+    /// Publishes the machine's progress and returns whether a stop was requested, clearing the
+    /// request
+    fn check(&self, cycles: u64) -> bool {
+        self.shared.cycles.store(cycles, Ordering::Relaxed);
+        self.shared.stop_requested.swap(false, Ordering::SeqCst)
+    }
+}
+
+impl<I: Io, M: MemoryBacking> LC3<I, M> {
+    /// Returns a handle other threads can use to stop `run` and watch its progress. `run` checks
+    /// the handle before every instruction.
+    pub fn run_control(&mut self) -> RunControl {
+        self.run_control
+            .get_or_insert_with(RunControl::default)
+            .clone()
+    }
+
+    /// Stops the machine if a stop was requested through its `RunControl`
+    pub(crate) fn check_run_control(&mut self) {
+        let stop_requested = match &self.run_control {
+            Some(control) => control.check(self.cycles),
+            None => false,
+        };
+        if stop_requested {
+            self.request_stop();
+        }
+    }
+}
@@ -0,0 +1,139 @@
+use std::fmt;
+
+use super::{
+    instruction::{DecodeMode, Instruction},
+    InstructionSize, MemoryLocationSize, RegisterIndex,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Operand {
+    Register(RegisterIndex),
+    Immediate(i16),
+    /// A PC-relative offset resolved to the address it refers to
+    Address(MemoryLocationSize),
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Operand::Register(r) => write!(f, "R{}", r),
+            Operand::Immediate(value) => write!(f, "#{}", value),
+            Operand::Address(address) => write!(f, "x{:04X}", address),
+        }
+    }
+}
+
+/// One disassembled word, broken into parts so front-ends can format it however they want. The
+/// `Display` impl formats it as assembly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DisasmLine {
+    pub mnemonic: String,
+    pub operands: Vec<Operand>,
+    pub comment: Option<String>,
+}
+
+impl fmt::Display for DisasmLine {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.mnemonic)?;
+        for (i, operand) in self.operands.iter().enumerate() {
+            let separator = if i == 0 { " " } else { ", " };
+            write!(f, "{}{}", separator, operand)?;
+        }
+        if let Some(comment) = &self.comment {
+            write!(f, " ; {}", comment)?;
+        }
+        Ok(())
+    }
+}
+
+/// Disassembles `word` as the instruction at `address`, which PC-relative operands are resolved
+/// against. Words that don't decode are disassembled as a `.FILL` with the reason as a comment.
+pub fn disassemble_structured(word: InstructionSize, address: MemoryLocationSize) -> DisasmLine {
+    use Operand::{Address, Immediate, Register};
+
+    let instr = match Instruction::try_decode(word, DecodeMode::Lenient) {
+        Ok(instr) => instr,
+        Err(e) => {
+            return DisasmLine {
+                mnemonic: ".FILL".to_string(),
+                operands: vec![Immediate(word as i16)],
+                comment: Some(e.to_string()),
+            }
+        }
+    };
+
+    let target = |offset: u16| Address(address.wrapping_add(1).wrapping_add(offset));
+    let (mnemonic, operands): (String, _) = match instr {
+        Instruction::AddImmediate(i) => (
+            "ADD".into(),
+            vec![Register(i.dr), Register(i.sr1), Immediate(i.imm5 as i16)],
+        ),
+        Instruction::AddRegister(i) => (
+            "ADD".into(),
+            vec![Register(i.dr), Register(i.sr1), Register(i.sr2)],
+        ),
+        Instruction::AndImmediate(i) => (
+            "AND".into(),
+            vec![Register(i.dr), Register(i.sr1), Immediate(i.imm5 as i16)],
+        ),
+        Instruction::AndRegister(i) => (
+            "AND".into(),
+            vec![Register(i.dr), Register(i.sr1), Register(i.sr2)],
+        ),
+        Instruction::Branch(i) if i.nzp.is_empty() => ("NOP".into(), vec![]),
+        Instruction::Branch(i) => (branch_mnemonic(word), vec![target(i.pc_offset9)]),
+        Instruction::Jump(i) if i.base_r == 7 => ("RET".into(), vec![]),
+        Instruction::Jump(i) => ("JMP".into(), vec![Register(i.base_r)]),
+        Instruction::JumpSubRoutineOffset(i) => ("JSR".into(), vec![target(i.pc_offset11)]),
+        Instruction::JumpSubRoutineRegister(i) => ("JSRR".into(), vec![Register(i.base_r)]),
+        Instruction::Load(i) => ("LD".into(), vec![Register(i.dr), target(i.pc_offset9)]),
+        Instruction::LoadBaseOffset(i) => (
+            "LDR".into(),
+            vec![
+                Register(i.dr),
+                Register(i.base_r),
+                Immediate(i.pc_offset6 as i16),
+            ],
+        ),
+        Instruction::LoadEffectiveAddress(i) => {
+            ("LEA".into(), vec![Register(i.dr), target(i.pc_offset9)])
+        }
+        Instruction::LoadIndirect(i) => ("LDI".into(), vec![Register(i.dr), target(i.pc_offset9)]),
+        Instruction::Not(i) => ("NOT".into(), vec![Register(i.dr), Register(i.sr1)]),
+        Instruction::Store(i) => ("ST".into(), vec![Register(i.sr), target(i.pc_offset9)]),
+        Instruction::StoreBaseOffset(i) => (
+            "STR".into(),
+            vec![
+                Register(i.sr),
+                Register(i.base_r),
+                Immediate(i.pc_offset6 as i16),
+            ],
+        ),
+        Instruction::StoreIndirect(i) => ("STI".into(), vec![Register(i.sr), target(i.pc_offset9)]),
+        Instruction::Trap(i) => (i.vect8.name().into(), vec![]),
+    };
+
+    DisasmLine {
+        mnemonic,
+        operands,
+        comment: None,
+    }
+}
+
+/// Disassembles `word` as the instruction at `address` into a line of assembly
+pub fn disassemble(word: InstructionSize, address: MemoryLocationSize) -> String {
+    disassemble_structured(word, address).to_string()
+}
+
+/// Returns "BR" followed by the condition codes the branch is taken on. The letters come straight
+/// from the LC-3 encoding, bit 11 for n, 10 for z and 9 for p, rather than from `CondFlag`,
+/// which orders N and Z the other way round.
+fn branch_mnemonic(word: InstructionSize) -> String {
+    let mut mnemonic = "BR".to_string();
+    for &(bit, letter) in &[(11, 'n'), (10, 'z'), (9, 'p')] {
+        if word & (1 << bit) != 0 {
+            mnemonic.push(letter);
+        }
+    }
+    mnemonic
+}
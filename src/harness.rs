@@ -0,0 +1,136 @@
+use std::ops::Range;
+
+use super::{
+    io::{BufferedIo, Io},
+    memory::MemoryBacking,
+    CondFlag, LoadError, MemoryLocationSize, RegisterSize, StopReason, LC3, REGISTER_COUNT,
+};
+
+/// Everything an autograder needs to know about how a program ran
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TestResult {
+    pub output: Vec<u8>,
+    pub registers: [RegisterSize; REGISTER_COUNT],
+    pub stop_reason: StopReason,
+    pub cycles: u64,
+}
+
+/// Loads `program`, feeds it `inputs` and runs it until it stops or has executed `max_cycles`
+/// instructions. An instruction that can't be executed stops the program with
+/// `StopReason::Fault` rather than panicking.
+pub fn run_test_case(
+    program: &[u8],
+    inputs: &[u8],
+    max_cycles: u64,
+) -> Result<TestResult, LoadError> {
+    let mut machine = LC3::from_bytes(program)?.with_io(BufferedIo::new(inputs));
+    let stop_reason = machine
+        .try_step_n(max_cycles as usize)
+        .unwrap_or(StopReason::CycleLimit);
+
+    Ok(TestResult {
+        output: machine.io.output,
+        registers: machine.registers,
+        stop_reason,
+        cycles: machine.cycles,
+    })
+}
+
+/// Whether `prog_a` and `prog_b` write the same output, finish with the same registers and stop
+/// for the same reason when both are run with `inputs` for up to `max_cycles` instructions, see
+/// `run_test_case`. Programs that fail to load are never equivalent.
+pub fn behaviorally_equivalent(
+    prog_a: &[u8],
+    prog_b: &[u8],
+    inputs: &[u8],
+    max_cycles: u64,
+) -> bool {
+    match (
+        run_test_case(prog_a, inputs, max_cycles),
+        run_test_case(prog_b, inputs, max_cycles),
+    ) {
+        (Ok(a), Ok(b)) => {
+            a.output == b.output && a.registers == b.registers && a.stop_reason == b.stop_reason
+        }
+        _ => false,
+    }
+}
+
+/// Builds a machine with `BufferedIo` and the listed instructions loaded at `PROGRAM_START`.
+/// Each instruction is written as the struct for its variant of `Instruction` and trap codes can
+/// be named without the `TrapCode::` prefix:
+///
+/// ```
+/// use lilc3::{lc3_asm, StopReason};
+///
+/// let mut machine = lc3_asm![
+///     AddImmediate { dr: 0, sr1: 0, imm5: 7 },
+///     Trap { vect8: Halt },
+/// ];
+/// assert_eq!(machine.run(), StopReason::Halted { code: 0 });
+/// assert_eq!(machine.registers[0], 7);
+/// ```
+#[macro_export]
+macro_rules! lc3_asm {
+    ($($variant:ident { $($field:ident: $value:expr),* $(,)? }),* $(,)?) => {{
+        #[allow(unused_imports)]
+        use $crate::instruction::TrapCode::*;
+
+        let words = $crate::instruction::encode_program(&[$(
+            $crate::instruction::Instruction::$variant(
+                $crate::instruction::$variant { $($field: $value),* }
+            )
+        ),*]);
+        let mut machine = $crate::LC3::from_start_state([0; $crate::MAX_MEMORY_SIZE])
+            .with_io($crate::io::BufferedIo::default());
+        machine
+            .load_words_at($crate::PROGRAM_START, &words)
+            .expect("Program doesn't fit in memory");
+        machine
+    }};
+}
+
+/// A snapshot of the machine's architectural state for comparing in tests, see `LC3::state`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmState {
+    pub registers: [RegisterSize; REGISTER_COUNT],
+    pub pc: MemoryLocationSize,
+    pub cond: CondFlag,
+    pub memory: Vec<MemoryLocationSize>,
+}
+
+impl VmState {
+    /// Whether the registers, PC and cond register match, regardless of what's in memory
+    pub fn state_eq_ignoring_memory(&self, other: &VmState) -> bool {
+        self.registers == other.registers && self.pc == other.pc && self.cond == other.cond
+    }
+}
+
+impl<I: Io, M: MemoryBacking> LC3<I, M> {
+    /// Returns a 64 bit FNV-1a hash of the words in `range`, taken over each word's big endian
+    /// bytes. The hash is stable across runs and versions so graders can compare a submission
+    /// against the hash of a known-good program.
+    pub fn program_hash(&self, range: Range<MemoryLocationSize>) -> u64 {
+        const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+        const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+        let mut hash = FNV_OFFSET_BASIS;
+        for address in range {
+            for &byte in &self.memory.read(address).to_be_bytes() {
+                hash ^= byte as u64;
+                hash = hash.wrapping_mul(FNV_PRIME);
+            }
+        }
+        hash
+    }
+
+    /// Returns a copy of the registers, PC, cond register and all of memory
+    pub fn state(&self) -> VmState {
+        VmState {
+            registers: self.registers,
+            pc: self.pc,
+            cond: self.cond,
+            memory: self.memory_image(),
+        }
+    }
+}
@@ -1,4 +1,6 @@
-use super::{CondFlag, InstructionSize, RegisterIndex};
+use std::{error::Error, fmt, str::FromStr};
+
+use super::{CondFlag, InstructionSize, MemoryLocationSize, RegisterIndex};
 
 /// OpCodes specify the instruction to be performed. In LC3 they are bits 12 to 15 of the 16 bit
 /// instruction. The numbers asssociated with each opcode in the enum correspond with bits 12 to 15 of an LC3 instruction for that opcode. That is, doing 12 right shifts on an instruction will leave
@@ -33,8 +35,6 @@ impl OpCode {
 
     /// `from_instruction` returns the OpCode for a particular instruction. The OpCode is bits 12 to
     /// 15 for an instruction
-    ///
-    /// # Panics if the opcode for the instruction is not recognized
     pub fn from_instruction(instruction: InstructionSize) -> Self {
         let opcode = get_opcode(instruction);
         match opcode {
@@ -46,17 +46,129 @@ impl OpCode {
             5 => OpCode::And,
             6 => OpCode::LoadBaseOffset,
             7 => OpCode::StoreBaseOffset,
+            8 => OpCode::Unused,
             9 => OpCode::Not,
             10 => OpCode::LoadIndirect,
             11 => OpCode::StoreIndirect,
             12 => OpCode::Jump,
+            13 => OpCode::Reserved,
             14 => OpCode::LoadEffectiveAddress,
-            15 => OpCode::Trap,
-            _ => todo!(),
+            _ => OpCode::Trap,
+        }
+    }
+}
+
+impl FromStr for OpCode {
+    type Err = ParseNameError;
+
+    /// Parses an assembly mnemonic, ignoring case. Branches can have any of their condition code
+    /// suffixes, e.g. `BRnz`, and RET and JSRR parse as the opcodes they share with JMP and JSR.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.to_ascii_uppercase();
+        let opcode = match upper.as_str() {
+            "ADD" => OpCode::Add,
+            "AND" => OpCode::And,
+            "JMP" | "RET" => OpCode::Jump,
+            "JSR" | "JSRR" => OpCode::JumpSubRoutine,
+            "LD" => OpCode::Load,
+            "LDI" => OpCode::LoadIndirect,
+            "LDR" => OpCode::LoadBaseOffset,
+            "LEA" => OpCode::LoadEffectiveAddress,
+            "NOT" => OpCode::Not,
+            "ST" => OpCode::Store,
+            "STI" => OpCode::StoreIndirect,
+            "STR" => OpCode::StoreBaseOffset,
+            "TRAP" => OpCode::Trap,
+            _ if is_branch_mnemonic(&upper) => OpCode::Branch,
+            _ => return Err(ParseNameError(s.to_string())),
+        };
+        Ok(opcode)
+    }
+}
+
+/// Whether `upper` is BR followed by N, Z and P each at most once and in that order
+fn is_branch_mnemonic(upper: &str) -> bool {
+    let mut flags = match upper.strip_prefix("BR") {
+        Some(flags) => flags,
+        None => return false,
+    };
+    for flag in &["N", "Z", "P"] {
+        flags = flags.strip_prefix(flag).unwrap_or(flags);
+    }
+    flags.is_empty()
+}
+
+/// Controls how forgiving decoding is about bits the spec requires to hold a fixed value
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+pub enum DecodeMode {
+    /// Reject instructions whose fixed bits don't match the spec
+    Strict,
+    /// Ignore fixed bits that don't affect what the instruction does
+    #[default]
+    Lenient,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The instruction uses an opcode that has no instruction associated with it
+    ReservedOpCode(InstructionSize),
+    /// The instruction has bits set incorrectly that the spec requires to hold a fixed value
+    MalformedInstruction(InstructionSize),
+    /// The instruction is a trap with a vector that isn't a known `TrapCode`
+    UnknownTrap(InstructionSize),
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DecodeError::ReservedOpCode(instr) => {
+                write!(f, "instruction {:#06x} uses a reserved opcode", instr)
+            }
+            DecodeError::MalformedInstruction(instr) => {
+                write!(f, "instruction {:#06x} is malformed", instr)
+            }
+            DecodeError::UnknownTrap(instr) => {
+                write!(f, "instruction {:#06x} is an unknown trap", instr)
+            }
         }
     }
 }
 
+impl Error for DecodeError {}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EncodeError {
+    /// The target is `distance` words from the instruction after the one being built, which
+    /// doesn't fit in its `bits` bit signed PC-relative offset
+    OffsetOutOfRange { distance: i32, bits: u8 },
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncodeError::OffsetOutOfRange { distance, bits } => write!(
+                f,
+                "offset {} doesn't fit in a {} bit PC-relative offset",
+                distance, bits
+            ),
+        }
+    }
+}
+
+impl Error for EncodeError {}
+
+/// A string that doesn't name an `OpCode` or `TrapCode`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseNameError(pub String);
+
+impl fmt::Display for ParseNameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "unrecognized name {:?}", self.0)
+    }
+}
+
+impl Error for ParseNameError {}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Instruction {
     AddImmediate(AddImmediate),
@@ -82,6 +194,8 @@ pub enum Instruction {
 pub struct AddImmediate {
     pub dr: RegisterIndex,
     pub sr1: RegisterIndex,
+    /// Already sign-extended to 16 bits when decoded, so it can be used as an operand as is.
+    /// Only the low 5 bits are encoded.
     pub imm5: u16,
 }
 
@@ -92,6 +206,7 @@ impl AddImmediate {
         let instr = set_dr(instr, self.dr);
         let instr = set_sr1(instr, self.sr1);
         let instr = set_imm5(instr, self.imm5);
+        debug_assert_eq!(get_immediate_mode(instr), 1);
 
         instr.to_be()
     }
@@ -119,6 +234,11 @@ impl AddRegister {
         let instr = set_dr(instr, self.dr);
         let instr = set_sr1(instr, self.sr1);
         let instr = set_sr2(instr, self.sr2);
+        debug_assert_eq!(
+            get_immediate_mode(instr),
+            0,
+            "sr2 spilled into the mode bit"
+        );
 
         instr.to_be()
     }
@@ -135,6 +255,8 @@ impl AddRegister {
 pub struct AndImmediate {
     pub dr: RegisterIndex,
     pub sr1: RegisterIndex,
+    /// Already sign-extended to 16 bits when decoded, so it can be used as an operand as is.
+    /// Only the low 5 bits are encoded.
     pub imm5: u16,
 }
 
@@ -145,6 +267,7 @@ impl AndImmediate {
         let instr = set_dr(instr, self.dr);
         let instr = set_sr1(instr, self.sr1);
         let instr = set_imm5(instr, self.imm5);
+        debug_assert_eq!(get_immediate_mode(instr), 1);
 
         instr.to_be()
     }
@@ -174,6 +297,11 @@ impl AndRegister {
         let instr = set_dr(instr, self.dr);
         let instr = set_sr1(instr, self.sr1);
         let instr = set_sr2(instr, self.sr2);
+        debug_assert_eq!(
+            get_immediate_mode(instr),
+            0,
+            "sr2 spilled into the mode bit"
+        );
 
         instr.to_be()
     }
@@ -193,6 +321,25 @@ pub struct Branch {
 }
 
 impl Branch {
+    /// Builds a branch to `target`. `current_pc` is the address the instruction will be at.
+    pub fn to_target(
+        nzp: CondFlag,
+        current_pc: MemoryLocationSize,
+        target: MemoryLocationSize,
+    ) -> Result<Self, EncodeError> {
+        let pc_offset9 = pc_offset_to(current_pc, target, 9)?;
+        Ok(Branch { nzp, pc_offset9 })
+    }
+
+    /// The canonical nop: a branch with no condition codes set, which is never taken whatever the
+    /// cond register holds. `pc_offset9` is ignored when it executes so it can hold any value.
+    pub fn nop(pc_offset9: u16) -> Self {
+        Branch {
+            nzp: CondFlag::empty(),
+            pc_offset9,
+        }
+    }
+
     pub fn encode(&self) -> InstructionSize {
         let instr = 0;
         let instr = set_opcode(instr, OpCode::Branch);
@@ -235,6 +382,15 @@ pub struct JumpSubRoutineOffset {
 }
 
 impl JumpSubRoutineOffset {
+    /// Builds a JSR to `target`. `current_pc` is the address the instruction will be at.
+    pub fn to_target(
+        current_pc: MemoryLocationSize,
+        target: MemoryLocationSize,
+    ) -> Result<Self, EncodeError> {
+        let pc_offset11 = pc_offset_to(current_pc, target, 11)?;
+        Ok(JumpSubRoutineOffset { pc_offset11 })
+    }
+
     pub fn encode(&self) -> InstructionSize {
         let instr = 0;
         let instr = set_opcode(instr, OpCode::JumpSubRoutine);
@@ -277,6 +433,16 @@ pub struct Load {
 }
 
 impl Load {
+    /// Builds an LD of `target` into `dr`. `current_pc` is the address the instruction will be at.
+    pub fn to_target(
+        dr: RegisterIndex,
+        current_pc: MemoryLocationSize,
+        target: MemoryLocationSize,
+    ) -> Result<Self, EncodeError> {
+        let pc_offset9 = pc_offset_to(current_pc, target, 9)?;
+        Ok(Load { dr, pc_offset9 })
+    }
+
     pub fn encode(&self) -> InstructionSize {
         let instr = 0;
         let instr = set_opcode(instr, OpCode::Load);
@@ -297,7 +463,9 @@ impl Load {
 pub struct LoadBaseOffset {
     pub dr: RegisterIndex,
     pub base_r: RegisterIndex,
-    pub pc_offset6: u8,
+    /// Already sign-extended to 16 bits when decoded, so it can be added to the base register as
+    /// is. Only the low 6 bits are encoded.
+    pub pc_offset6: u16,
 }
 
 impl LoadBaseOffset {
@@ -330,6 +498,16 @@ pub struct LoadEffectiveAddress {
 }
 
 impl LoadEffectiveAddress {
+    /// Builds an LEA of `target` into `dr`. `current_pc` is the address the instruction will be at.
+    pub fn to_target(
+        dr: RegisterIndex,
+        current_pc: MemoryLocationSize,
+        target: MemoryLocationSize,
+    ) -> Result<Self, EncodeError> {
+        let pc_offset9 = pc_offset_to(current_pc, target, 9)?;
+        Ok(LoadEffectiveAddress { dr, pc_offset9 })
+    }
+
     pub fn encode(&self) -> InstructionSize {
         let instr = 0;
         let instr = set_opcode(instr, OpCode::LoadEffectiveAddress);
@@ -353,6 +531,17 @@ pub struct LoadIndirect {
 }
 
 impl LoadIndirect {
+    /// Builds an LDI through `target` into `dr`. `current_pc` is the address the instruction will
+    /// be at.
+    pub fn to_target(
+        dr: RegisterIndex,
+        current_pc: MemoryLocationSize,
+        target: MemoryLocationSize,
+    ) -> Result<Self, EncodeError> {
+        let pc_offset9 = pc_offset_to(current_pc, target, 9)?;
+        Ok(LoadIndirect { dr, pc_offset9 })
+    }
+
     pub fn encode(&self) -> InstructionSize {
         let instr = 0;
         let instr = set_opcode(instr, OpCode::LoadIndirect);
@@ -369,6 +558,22 @@ impl LoadIndirect {
     }
 }
 
+/// Bits 0 to 5 of a NOT instruction are always set
+const NOT_FIXED_BITS: InstructionSize = 0x3F;
+
+/// Bits 3 and 4 of the register forms of ADD and AND, which the spec requires to be clear
+const REGISTER_MODE_FIXED_BITS: InstructionSize = 0x18;
+
+/// Returns an error if bits 3 and 4 of a register form ADD or AND aren't clear as the spec
+/// requires
+fn validate_register_mode(instr: InstructionSize) -> Result<(), DecodeError> {
+    if instr & REGISTER_MODE_FIXED_BITS == 0 {
+        Ok(())
+    } else {
+        Err(DecodeError::MalformedInstruction(instr))
+    }
+}
+
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub struct Not {
     pub dr: RegisterIndex,
@@ -381,7 +586,7 @@ impl Not {
         let instr = set_opcode(instr, OpCode::Not);
         let instr = set_dr(instr, self.dr);
         let instr = set_sr1(instr, self.sr1);
-        let instr = instr | 0x1F;
+        let instr = instr | NOT_FIXED_BITS;
 
         instr.to_be()
     }
@@ -392,6 +597,15 @@ impl Not {
 
         Not { dr, sr1 }
     }
+
+    /// Returns an error if bits 0 to 5 of `instr` aren't all set as the spec requires
+    pub fn validate(instr: u16) -> Result<(), DecodeError> {
+        if instr & NOT_FIXED_BITS == NOT_FIXED_BITS {
+            Ok(())
+        } else {
+            Err(DecodeError::MalformedInstruction(instr))
+        }
+    }
 }
 
 #[derive(Debug, Copy, Clone, PartialEq, Eq)]
@@ -401,6 +615,16 @@ pub struct Store {
 }
 
 impl Store {
+    /// Builds an ST of `sr` to `target`. `current_pc` is the address the instruction will be at.
+    pub fn to_target(
+        sr: RegisterIndex,
+        current_pc: MemoryLocationSize,
+        target: MemoryLocationSize,
+    ) -> Result<Self, EncodeError> {
+        let pc_offset9 = pc_offset_to(current_pc, target, 9)?;
+        Ok(Store { sr, pc_offset9 })
+    }
+
     pub fn encode(&self) -> InstructionSize {
         let instr = 0;
         let instr = set_opcode(instr, OpCode::Store);
@@ -421,7 +645,9 @@ impl Store {
 pub struct StoreBaseOffset {
     pub sr: RegisterIndex,
     pub base_r: RegisterIndex,
-    pub pc_offset6: u8,
+    /// Already sign-extended to 16 bits when decoded, so it can be added to the base register as
+    /// is. Only the low 6 bits are encoded.
+    pub pc_offset6: u16,
 }
 
 impl StoreBaseOffset {
@@ -454,6 +680,17 @@ pub struct StoreIndirect {
 }
 
 impl StoreIndirect {
+    /// Builds an STI of `sr` through `target`. `current_pc` is the address the instruction will
+    /// be at.
+    pub fn to_target(
+        sr: RegisterIndex,
+        current_pc: MemoryLocationSize,
+        target: MemoryLocationSize,
+    ) -> Result<Self, EncodeError> {
+        let pc_offset9 = pc_offset_to(current_pc, target, 9)?;
+        Ok(StoreIndirect { sr, pc_offset9 })
+    }
+
     pub fn encode(&self) -> InstructionSize {
         let instr = 0;
         let instr = set_opcode(instr, OpCode::StoreIndirect);
@@ -492,14 +729,27 @@ impl Trap {
 }
 
 impl Instruction {
+    /// Decodes `instr` leniently
+    ///
+    /// # Panics if the instruction uses a reserved opcode
     pub fn decode(instr: InstructionSize) -> Self {
-        match OpCode::from_instruction(instr) {
+        match Self::try_decode(instr, DecodeMode::Lenient) {
+            Ok(instruction) => instruction,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_decode(instr: InstructionSize, mode: DecodeMode) -> Result<Self, DecodeError> {
+        let instruction = match OpCode::from_instruction(instr) {
             OpCode::Add => {
                 let mode_flag = get_immediate_mode(instr);
 
                 if mode_flag == 1 {
                     Instruction::AddImmediate(AddImmediate::decode(instr))
                 } else {
+                    if mode == DecodeMode::Strict {
+                        validate_register_mode(instr)?;
+                    }
                     Instruction::AddRegister(AddRegister::decode(instr))
                 }
             }
@@ -509,6 +759,9 @@ impl Instruction {
                 if mode_flag == 1 {
                     Instruction::AndImmediate(AndImmediate::decode(instr))
                 } else {
+                    if mode == DecodeMode::Strict {
+                        validate_register_mode(instr)?;
+                    }
                     Instruction::AndRegister(AndRegister::decode(instr))
                 }
             }
@@ -529,12 +782,60 @@ impl Instruction {
                 Instruction::LoadEffectiveAddress(LoadEffectiveAddress::decode(instr))
             }
             OpCode::LoadIndirect => Instruction::LoadIndirect(LoadIndirect::decode(instr)),
-            OpCode::Not => Instruction::Not(Not::decode(instr)),
+            OpCode::Not => {
+                if mode == DecodeMode::Strict {
+                    Not::validate(instr)?;
+                }
+                Instruction::Not(Not::decode(instr))
+            }
             OpCode::Store => Instruction::Store(Store::decode(instr)),
             OpCode::StoreBaseOffset => Instruction::StoreBaseOffset(StoreBaseOffset::decode(instr)),
             OpCode::StoreIndirect => Instruction::StoreIndirect(StoreIndirect::decode(instr)),
-            OpCode::Trap => Instruction::Trap(Trap::decode(instr)),
-            _ => todo!(),
+            OpCode::Trap => {
+                let vect8 = get_bit_field(instr, 0, 8) as u8;
+                if TrapCode::try_from_bits(vect8).is_none() {
+                    return Err(DecodeError::UnknownTrap(instr));
+                }
+                Instruction::Trap(Trap::decode(instr))
+            }
+            OpCode::Reserved | OpCode::Unused => return Err(DecodeError::ReservedOpCode(instr)),
+        };
+
+        Ok(instruction)
+    }
+
+    /// Returns the registers whose values the instruction reads, including the ones traps read
+    /// implicitly
+    pub fn source_registers(&self) -> Vec<RegisterIndex> {
+        match self {
+            Self::AddImmediate(instr) => vec![instr.sr1],
+            Self::AddRegister(instr) => vec![instr.sr1, instr.sr2],
+            Self::AndImmediate(instr) => vec![instr.sr1],
+            Self::AndRegister(instr) => vec![instr.sr1, instr.sr2],
+            Self::Jump(instr) => vec![instr.base_r],
+            Self::JumpSubRoutineRegister(instr) => vec![instr.base_r],
+            Self::LoadBaseOffset(instr) => vec![instr.base_r],
+            Self::Not(instr) => vec![instr.sr1],
+            Self::Store(instr) => vec![instr.sr],
+            Self::StoreBaseOffset(instr) => vec![instr.sr, instr.base_r],
+            Self::StoreIndirect(instr) => vec![instr.sr],
+            Self::Trap(instr) => match instr.vect8 {
+                TrapCode::Out
+                | TrapCode::Puts
+                | TrapCode::PutsP
+                | TrapCode::Gets
+                | TrapCode::PutN => vec![0],
+                TrapCode::GetC
+                | TrapCode::In
+                | TrapCode::Halt
+                | TrapCode::ClrScr
+                | TrapCode::Flush => vec![],
+            },
+            Self::Branch(_)
+            | Self::JumpSubRoutineOffset(_)
+            | Self::Load(_)
+            | Self::LoadEffectiveAddress(_)
+            | Self::LoadIndirect(_) => vec![],
         }
     }
 
@@ -561,6 +862,36 @@ impl Instruction {
     }
 }
 
+/// Leniently decodes each word of a program without needing a machine to run it on
+pub fn decode_program(words: &[InstructionSize]) -> Vec<Result<Instruction, DecodeError>> {
+    words
+        .iter()
+        .map(|&word| Instruction::try_decode(word, DecodeMode::Lenient))
+        .collect()
+}
+
+/// Encodes each instruction of a program as a word in native byte order, ready for
+/// `LC3::load_words_at`. Unlike `Instruction::encode` the words aren't byte swapped to big endian.
+pub fn encode_program(instrs: &[Instruction]) -> Vec<InstructionSize> {
+    instrs
+        .iter()
+        .map(|instr| InstructionSize::from_be(instr.encode()))
+        .collect()
+}
+
+/// Whether `a` and `b` encode the same instruction, ignoring bits the instruction doesn't use,
+/// e.g. the low bits of NOT or the unused bits of JMP. Words that don't decode are only
+/// equivalent if they're identical.
+pub fn instructions_equivalent(a: InstructionSize, b: InstructionSize) -> bool {
+    match (
+        Instruction::try_decode(a, DecodeMode::Lenient),
+        Instruction::try_decode(b, DecodeMode::Lenient),
+    ) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
 /// Returns the bits of an instruction from `start` to `end`
 ///
 /// Instruction bits are 0 indexed. `start` is inclusive and `end` is exclusive.
@@ -579,7 +910,7 @@ fn set_opcode(instr: InstructionSize, op: OpCode) -> InstructionSize {
     set_bit_field(instr, op as u16, 12)
 }
 
-fn get_opcode(instr: InstructionSize) -> u16 {
+pub(crate) fn get_opcode(instr: InstructionSize) -> u16 {
     get_bit_field(instr, 12, 16)
 }
 
@@ -587,7 +918,7 @@ fn set_dr(instr: InstructionSize, register: RegisterIndex) -> InstructionSize {
     set_bit_field(instr, register as u16, 9)
 }
 
-fn get_dr(instr: InstructionSize) -> RegisterIndex {
+pub(crate) fn get_dr(instr: InstructionSize) -> RegisterIndex {
     get_bit_field(instr, 9, 12) as u8
 }
 
@@ -595,7 +926,7 @@ fn set_sr1(instr: InstructionSize, register: RegisterIndex) -> InstructionSize {
     set_bit_field(instr, register as u16, 6)
 }
 
-fn get_sr1(instr: InstructionSize) -> RegisterIndex {
+pub(crate) fn get_sr1(instr: InstructionSize) -> RegisterIndex {
     get_bit_field(instr, 6, 9) as u8
 }
 
@@ -603,28 +934,28 @@ fn set_sr2(instr: InstructionSize, register: RegisterIndex) -> InstructionSize {
     set_bit_field(instr, register as u16, 0)
 }
 
-fn get_sr2(instr: InstructionSize) -> RegisterIndex {
+pub(crate) fn get_sr2(instr: InstructionSize) -> RegisterIndex {
     get_bit_field(instr, 0, 3) as u8
 }
 
+/// Only the low 5 bits of `imm5` are used so a sign-extended immediate doesn't clobber the mode
+/// bit or the registers
 fn set_imm5(instr: InstructionSize, imm5: u16) -> InstructionSize {
-    let instr = set_bit_field(instr, imm5, 0);
+    let instr = set_bit_field(instr, imm5 & 0x1F, 0);
     let immediate_mode_flag = 0b100000;
-    let instr = instr | immediate_mode_flag;
-    instr
+    instr | immediate_mode_flag
 }
 
-fn get_imm5(instr: InstructionSize) -> u16 {
+pub(crate) fn get_imm5(instr: InstructionSize) -> u16 {
     let imm5 = get_bit_field(instr, 0, 5);
-    let imm5 = sign_extend_u16(imm5, 5);
-    imm5
+    sign_extend_u16(imm5, 5)
 }
 
-fn get_immediate_mode(instr: InstructionSize) -> u16 {
+pub(crate) fn get_immediate_mode(instr: InstructionSize) -> u16 {
     get_bit_field(instr, 5, 6)
 }
 
-fn get_nzp(instr: InstructionSize) -> CondFlag {
+pub(crate) fn get_nzp(instr: InstructionSize) -> CondFlag {
     let cond = get_bit_field(instr, 9, 12);
     CondFlag::from_bits(cond as u8).unwrap()
 }
@@ -634,7 +965,7 @@ fn set_nzp(instr: InstructionSize, cond: CondFlag) -> InstructionSize {
 }
 
 fn get_base_r(instr: InstructionSize) -> RegisterIndex {
-    get_bit_field(instr, 6, 8) as u8
+    get_bit_field(instr, 6, 9) as u8
 }
 
 fn set_base_r(instr: InstructionSize, base_r: RegisterIndex) -> InstructionSize {
@@ -649,31 +980,51 @@ fn set_pc_offset_mode(instr: InstructionSize) -> u16 {
     set_bit_field(instr, 1, 11)
 }
 
-fn get_pc_offset6(instr: InstructionSize) -> u8 {
+fn get_pc_offset6(instr: InstructionSize) -> u16 {
     let pc_offset6 = get_bit_field(instr, 0, 6);
-    sign_extend_u16(pc_offset6, 6) as u8
+    sign_extend_u16(pc_offset6, 6)
+}
+
+fn set_pc_offset6(instr: InstructionSize, offset: u16) -> InstructionSize {
+    set_bit_field(instr, offset & 0x3F, 0)
 }
 
-fn set_pc_offset6(instr: InstructionSize, offset: u8) -> InstructionSize {
-    set_bit_field(instr, offset as u16, 0)
+/// Returns the sign-extended offset from the instruction at `current_pc` to `target`, which is
+/// relative to the incremented PC, if it fits in a `bits` bit offset field. Addresses wrap around
+/// like the PC does.
+fn pc_offset_to(
+    current_pc: MemoryLocationSize,
+    target: MemoryLocationSize,
+    bits: u8,
+) -> Result<u16, EncodeError> {
+    let distance = target.wrapping_sub(current_pc.wrapping_add(1)) as i16 as i32;
+    let limit = 1 << (bits - 1);
+    if distance < -limit || distance >= limit {
+        return Err(EncodeError::OffsetOutOfRange { distance, bits });
+    }
+    Ok(distance as u16)
 }
 
-fn get_pc_offset9(instr: InstructionSize) -> u16 {
+pub(crate) fn get_pc_offset9(instr: InstructionSize) -> u16 {
     let pc_offset9 = get_bit_field(instr, 0, 9);
     sign_extend_u16(pc_offset9, 9)
 }
 
+/// Only the low 9 bits of `offset` are used so a sign-extended offset doesn't clobber the rest of
+/// the instruction
 fn set_pc_offset9(instr: InstructionSize, offset: u16) -> InstructionSize {
-    set_bit_field(instr, offset, 0)
+    set_bit_field(instr, offset & 0x1FF, 0)
 }
 
 fn get_pc_offset11(instr: InstructionSize) -> u16 {
     let pc_offset11 = get_bit_field(instr, 0, 11);
-    sign_extend_u16(pc_offset11, 9)
+    sign_extend_u16(pc_offset11, 11)
 }
 
+/// Only the low 11 bits of `offset` are used so a sign-extended offset doesn't clobber the mode
+/// bit or the opcode
 fn set_pc_offset11(instr: InstructionSize, offset: u16) -> InstructionSize {
-    set_bit_field(instr, offset, 0)
+    set_bit_field(instr, offset & 0x7FF, 0)
 }
 
 fn get_sr(instr: InstructionSize) -> RegisterIndex {
@@ -684,7 +1035,7 @@ fn set_sr(instr: InstructionSize, sr: u8) -> InstructionSize {
     set_bit_field(instr, sr as u16, 9)
 }
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 #[repr(u8)]
 pub enum TrapCode {
     GetC = 0x20,
@@ -693,22 +1044,86 @@ pub enum TrapCode {
     In = 0x23,
     PutsP = 0x24,
     Halt = 0x25,
+    /// Reads a line of input into memory starting at the address in R0. The newline isn't stored
+    /// and the line is null-terminated. The number of characters read is put in R1.
+    Gets = 0x26,
+    /// Writes the value in R0 as a signed decimal integer
+    PutN = 0x27,
+    /// Writes the ANSI escape sequence to clear the screen and move the cursor to the top left
+    ClrScr = 0x28,
+    /// Flushes any buffered output
+    Flush = 0x29,
 }
 
 impl TrapCode {
+    /// Returns the name the assembler uses for the trap, e.g. "GETC"
+    pub fn name(&self) -> &'static str {
+        match self {
+            TrapCode::GetC => "GETC",
+            TrapCode::Out => "OUT",
+            TrapCode::Puts => "PUTS",
+            TrapCode::In => "IN",
+            TrapCode::PutsP => "PUTSP",
+            TrapCode::Halt => "HALT",
+            TrapCode::Gets => "GETS",
+            TrapCode::PutN => "PUTN",
+            TrapCode::ClrScr => "CLRSCR",
+            TrapCode::Flush => "FLUSH",
+        }
+    }
+
+    /// Extended traps aren't part of the LC3 spec and are only executed when the machine has
+    /// extended traps enabled
+    pub fn is_extended(&self) -> bool {
+        matches!(
+            self,
+            TrapCode::Gets | TrapCode::PutN | TrapCode::ClrScr | TrapCode::Flush
+        )
+    }
+
+    /// # Panics if `bits` isn't a recognized trap code
     pub fn from_bits(bits: u8) -> Self {
+        Self::try_from_bits(bits).expect("Unrecognized trap code")
+    }
+
+    pub fn try_from_bits(bits: u8) -> Option<Self> {
         match bits {
-            0x20 => TrapCode::GetC,
-            0x21 => TrapCode::Out,
-            0x22 => TrapCode::Puts,
-            0x23 => TrapCode::In,
-            0x24 => TrapCode::PutsP,
-            0x25 => TrapCode::Halt,
-            _ => panic!("Unrecognized trap code"),
+            0x20 => Some(TrapCode::GetC),
+            0x21 => Some(TrapCode::Out),
+            0x22 => Some(TrapCode::Puts),
+            0x23 => Some(TrapCode::In),
+            0x24 => Some(TrapCode::PutsP),
+            0x25 => Some(TrapCode::Halt),
+            0x26 => Some(TrapCode::Gets),
+            0x27 => Some(TrapCode::PutN),
+            0x28 => Some(TrapCode::ClrScr),
+            0x29 => Some(TrapCode::Flush),
+            _ => None,
         }
     }
 }
 
+impl FromStr for TrapCode {
+    type Err = ParseNameError;
+
+    /// Parses the name of a trap, ignoring case, or its vector as hex in the assembler's `x25`
+    /// form or Rust's `0x25` form
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let upper = s.to_ascii_uppercase();
+        let trap = (0..=u8::MAX)
+            .filter_map(TrapCode::try_from_bits)
+            .find(|code| code.name() == upper)
+            .or_else(|| {
+                upper
+                    .strip_prefix("0X")
+                    .or_else(|| upper.strip_prefix('X'))
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .and_then(TrapCode::try_from_bits)
+            });
+        trap.ok_or_else(|| ParseNameError(s.to_string()))
+    }
+}
+
 fn get_trap_vect8(instr: InstructionSize) -> TrapCode {
     let vect8 = get_bit_field(instr, 0, 8);
     TrapCode::from_bits(vect8 as u8)
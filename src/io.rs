@@ -0,0 +1,216 @@
+use std::{
+    collections::VecDeque,
+    io::{self, Read, Write},
+};
+
+/// `Io` is where the machine's traps read input from and write output to.
+pub trait Io {
+    /// Returns the next byte of input or `None` if the input is exhausted
+    fn read_byte(&mut self) -> Option<u8>;
+
+    fn write_bytes(&mut self, bytes: &[u8]);
+
+    fn flush(&mut self);
+}
+
+/// Reads from the process's stdin and writes to its stdout
+#[derive(Debug, Default)]
+pub struct StdIo;
+
+impl Io for StdIo {
+    fn read_byte(&mut self) -> Option<u8> {
+        let mut buf = [0; 1];
+        io::stdin().read_exact(&mut buf).ok()?;
+        Some(buf[0])
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        io::stdout().write_all(bytes).expect("Write failed");
+    }
+
+    fn flush(&mut self) {
+        io::stdout().flush().expect("Flush failed");
+    }
+}
+
+/// Reads from a fixed buffer of input and collects everything written in `output`. Useful for
+/// tests and for running programs headless.
+#[derive(Debug, Default)]
+pub struct BufferedIo {
+    pub input: VecDeque<u8>,
+    pub output: Vec<u8>,
+}
+
+impl BufferedIo {
+    pub fn new(input: &[u8]) -> Self {
+        BufferedIo {
+            input: input.iter().copied().collect(),
+            output: Vec::new(),
+        }
+    }
+}
+
+impl Io for BufferedIo {
+    fn read_byte(&mut self) -> Option<u8> {
+        self.input.pop_front()
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.output.extend_from_slice(bytes);
+    }
+
+    fn flush(&mut self) {}
+}
+
+/// Like `BufferedIo` but decodes the bytes written as UTF-8 into `output`, so a character can be
+/// written a byte at a time across several OUT traps. Invalid sequences are replaced with
+/// U+FFFD.
+#[derive(Debug, Default)]
+pub struct Utf8Io {
+    pub input: VecDeque<u8>,
+    pub output: String,
+    /// Bytes of a character that hasn't been completely written yet
+    pending: Vec<u8>,
+}
+
+impl Utf8Io {
+    pub fn new(input: &[u8]) -> Self {
+        Utf8Io {
+            input: input.iter().copied().collect(),
+            ..Default::default()
+        }
+    }
+}
+
+impl Io for Utf8Io {
+    fn read_byte(&mut self) -> Option<u8> {
+        self.input.pop_front()
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+        loop {
+            match std::str::from_utf8(&self.pending) {
+                Ok(s) => {
+                    self.output.push_str(s);
+                    self.pending.clear();
+                    return;
+                }
+                Err(e) => {
+                    let valid = e.valid_up_to();
+                    // Safe to unwrap since everything before valid_up_to is valid UTF-8
+                    self.output
+                        .push_str(std::str::from_utf8(&self.pending[..valid]).unwrap());
+                    match e.error_len() {
+                        Some(len) => {
+                            self.output.push(char::REPLACEMENT_CHARACTER);
+                            self.pending.drain(..valid + len);
+                        }
+                        None => {
+                            // The rest is the start of a character that may be finished by a
+                            // later write
+                            self.pending.drain(..valid);
+                            return;
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn flush(&mut self) {}
+}
+
+/// Passes everything through to `inner` while keeping a copy of every byte of input read in
+/// `recorded`, see `session::Session`
+#[derive(Debug, Default)]
+pub struct RecordingIo<I: Io> {
+    pub inner: I,
+    pub recorded: Vec<u8>,
+}
+
+impl<I: Io> RecordingIo<I> {
+    pub fn new(inner: I) -> Self {
+        RecordingIo {
+            inner,
+            recorded: Vec::new(),
+        }
+    }
+}
+
+impl<I: Io> Io for RecordingIo<I> {
+    fn read_byte(&mut self) -> Option<u8> {
+        let byte = self.inner.read_byte()?;
+        self.recorded.push(byte);
+        Some(byte)
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.inner.write_bytes(bytes)
+    }
+
+    fn flush(&mut self) {
+        self.inner.flush()
+    }
+}
+
+/// Passes everything through to `inner` while keeping a copy of all output in `captured`, e.g. to
+/// show a program's output on the terminal and still be able to check it afterwards
+#[derive(Debug, Default)]
+pub struct TeeIo<I: Io> {
+    pub inner: I,
+    pub captured: Vec<u8>,
+}
+
+impl<I: Io> TeeIo<I> {
+    pub fn new(inner: I) -> Self {
+        TeeIo {
+            inner,
+            captured: Vec::new(),
+        }
+    }
+}
+
+impl<I: Io> Io for TeeIo<I> {
+    fn read_byte(&mut self) -> Option<u8> {
+        self.inner.read_byte()
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.captured.extend_from_slice(bytes);
+        self.inner.write_bytes(bytes)
+    }
+
+    fn flush(&mut self) {
+        self.inner.flush()
+    }
+}
+
+/// Reads input from any `Read` and writes output to any `Write`, e.g. a socket, pipe or file
+#[derive(Debug, Default)]
+pub struct ReadWriteIo<R: Read, W: Write> {
+    pub reader: R,
+    pub writer: W,
+}
+
+impl<R: Read, W: Write> ReadWriteIo<R, W> {
+    pub fn new(reader: R, writer: W) -> Self {
+        ReadWriteIo { reader, writer }
+    }
+}
+
+impl<R: Read, W: Write> Io for ReadWriteIo<R, W> {
+    fn read_byte(&mut self) -> Option<u8> {
+        let mut buf = [0; 1];
+        self.reader.read_exact(&mut buf).ok()?;
+        Some(buf[0])
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.writer.write_all(bytes).expect("Write failed");
+    }
+
+    fn flush(&mut self) {
+        self.writer.flush().expect("Flush failed");
+    }
+}
@@ -1,13 +1,38 @@
 use bitflags::bitflags;
-use std::io::{self, Read, Write};
+use std::{
+    collections::HashMap,
+    convert::TryFrom,
+    error::Error,
+    fmt,
+    io::Write,
+    ops::Range,
+    time::{Duration, Instant},
+};
 
+pub mod analysis;
+pub mod assembler;
+pub mod control;
+pub mod disassembler;
+pub mod harness;
 pub mod instruction;
-
+pub mod io;
+pub mod memory;
+pub mod session;
+pub mod stats;
+#[cfg(feature = "raw_terminal")]
+pub mod terminal;
+pub mod trace;
+
+use control::RunControl;
 use instruction::{
-    AddImmediate, AddRegister, AndImmediate, AndRegister, Branch, Instruction, Jump,
+    get_dr, get_imm5, get_immediate_mode, get_nzp, get_pc_offset9, get_sr1, get_sr2, AddImmediate,
+    AddRegister, AndImmediate, AndRegister, Branch, DecodeError, DecodeMode, Instruction, Jump,
     JumpSubRoutineOffset, JumpSubRoutineRegister, Load, LoadBaseOffset, LoadEffectiveAddress,
-    LoadIndirect, Not, Store, StoreBaseOffset, StoreIndirect, Trap, TrapCode,
+    LoadIndirect, Not, OpCode, Store, StoreBaseOffset, StoreIndirect, Trap, TrapCode,
 };
+use io::{Io, StdIo};
+use memory::{AddressSet, MemoryBacking};
+use stats::BranchStats;
 
 pub type BusSize = u16;
 pub type InstructionBytes = [u8; 2];
@@ -17,9 +42,38 @@ pub type MemoryLocationSize = u16;
 pub type RegisterIndex = u8;
 pub type RegisterSize = u16;
 
-const PROGRAM_START: MemoryLocationSize = 0x3000;
-const MAX_MEMORY_SIZE: usize = BusSize::MAX as usize;
-const REGISTER_COUNT: usize = 8;
+/// The address programs start executing from when the machine isn't given an origin
+pub const PROGRAM_START: MemoryLocationSize = 0x3000;
+
+/// The number of memory locations the machine can address
+///
+/// ```
+/// let machine = lilc3::LC3::from_start_state([0; lilc3::MAX_MEMORY_SIZE]);
+/// assert_eq!(machine.memory.len(), 0x10000);
+/// ```
+pub const MAX_MEMORY_SIZE: usize = BusSize::MAX as usize + 1;
+
+/// The number of general purpose registers
+pub const REGISTER_COUNT: usize = 8;
+
+/// Clears the screen and moves the cursor to the top left
+const ANSI_CLEAR_SCREEN: &[u8] = b"\x1b[2J\x1b[H";
+
+/// The last address of the trap and interrupt vector tables, which start at address 0
+const VECTOR_TABLE_END: MemoryLocationSize = 0x01FF;
+
+/// The first address of the device registers, which run to the end of memory
+const DEVICE_REGISTERS_START: MemoryLocationSize = 0xFE00;
+
+/// The machine control register. Clearing its high bit stops the machine, which is how the HALT
+/// routine in the standard LC3 operating system halts.
+pub const MCR_ADDRESS: MemoryLocationSize = 0xFFFE;
+
+/// The number of instructions `run_with_timeout` executes between checks of the clock
+pub const TIMEOUT_CHECK_INTERVAL: usize = 1024;
+
+/// The number of instructions `run_generator` executes each time it is pumped
+pub const GENERATOR_SLICE: usize = 1000;
 
 bitflags! {
     pub struct CondFlag: u8 {
@@ -29,51 +83,610 @@ bitflags! {
     }
 }
 
-pub struct LC3 {
-    pub memory: Memory,
+/// Why the machine stopped executing instructions
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StopReason {
+    /// The program executed the HALT trap or cleared the high bit of the MCR, see `MCR_ADDRESS`.
+    /// `code` is the value of R0 at the time if the machine has `halt_exit_code` enabled and 0
+    /// otherwise.
+    Halted { code: RegisterSize },
+    /// The program tried to read input after all of it was consumed
+    InputExhausted,
+    /// The program executed as many instructions as it was allowed to
+    CycleLimit,
+    /// The program tried to write to memory more times than `LC3::write_limit` allows. The write
+    /// that went over the limit wasn't made.
+    WriteLimitExceeded,
+    /// A load read an address that was never loaded or written, see `LC3::trap_uninit_reads`.
+    /// The load didn't change any registers.
+    UninitRead(MemoryLocationSize),
+    /// The instruction at this address tried to write R7 even though it isn't a JSR or JSRR, see
+    /// `LC3::stop_on_r7_write`. The write wasn't made.
+    UnexpectedR7Write(MemoryLocationSize),
+    /// The register being watched by `LC3::run_until_register` reached the value it was waiting
+    /// for
+    RegisterMatched,
+    /// A JSR or JSRR would have nested calls deeper than `LC3::max_call_depth`. The call wasn't
+    /// made.
+    RecursionLimit,
+    /// The instruction at this address couldn't be executed. Only reported by `LC3::try_step_n`;
+    /// the other ways of running the machine panic instead. The PC is left at the instruction.
+    Fault(MemoryLocationSize, StepError),
+    /// The program ran for longer than `LC3::run_with_timeout` allowed
+    Timeout,
+    /// Something outside the program stopped the machine, e.g. a trap handler calling
+    /// `LC3::request_stop` or setting `running` to false
+    Stopped,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum LoadError {
+    /// The program is too short to contain its origin
+    MissingOrigin,
+    /// The program ends partway through a word
+    OddByteCount,
+    /// The program runs past the end of memory when placed at `origin`
+    TooLarge {
+        origin: MemoryLocationSize,
+        len: usize,
+    },
+    /// The program overlaps the operating system or device registers when placed at `origin`, see
+    /// `LC3::protect_system_memory`
+    ReservedRegion {
+        origin: MemoryLocationSize,
+        len: usize,
+    },
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::MissingOrigin => write!(f, "program is missing its origin"),
+            LoadError::OddByteCount => write!(f, "program ends partway through a word"),
+            LoadError::TooLarge { origin, len } => write!(
+                f,
+                "program of {} words doesn't fit in memory at origin {:#06x}",
+                len, origin
+            ),
+            LoadError::ReservedRegion { origin, len } => write!(
+                f,
+                "program of {} words at origin {:#06x} overlaps system memory",
+                len, origin
+            ),
+        }
+    }
+}
+
+impl Error for LoadError {}
+
+/// Something suspicious the program did that doesn't stop it from running
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Warning {
+    /// The JSR/JSRR at this address overwrote a return address in R7 that was never used by a RET
+    /// or saved anywhere else
+    ClobberedReturnAddress(MemoryLocationSize),
+    /// The instruction at this address read a register that nothing had written yet
+    UninitializedRegister(MemoryLocationSize, RegisterIndex),
+    /// The word at this address didn't decode and was skipped, see `LC3::skip_decode_errors`
+    SkippedWord(MemoryLocationSize, InstructionSize),
+    /// The program wrote to this address in the trap or interrupt vector table, see
+    /// `LC3::lint_vector_table_writes`
+    VectorTableWrite(MemoryLocationSize),
+    /// The PC moved into the trap or interrupt vector table and the word at this address was
+    /// fetched as an instruction, see `LC3::lint_vector_table_execution`
+    ExecutingVectorTable(MemoryLocationSize),
+    /// The TRAP at this address used this vector, which isn't a known trap, and was executed as a
+    /// no-op, see `LC3::ignore_unknown_traps`
+    UnknownTrap(MemoryLocationSize, u8),
+}
+
+/// Why an instruction couldn't be executed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum StepError {
+    Decode(DecodeError),
+    /// The instruction is an extended trap but the machine doesn't have extended traps enabled or
+    /// a handler for the trap
+    ExtendedTrapDisabled(TrapCode),
+}
+
+impl From<DecodeError> for StepError {
+    fn from(e: DecodeError) -> Self {
+        StepError::Decode(e)
+    }
+}
+
+impl fmt::Display for StepError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StepError::Decode(e) => e.fmt(f),
+            StepError::ExtendedTrapDisabled(code) => write!(
+                f,
+                "{:?} is an extended trap but extended traps are disabled",
+                code
+            ),
+        }
+    }
+}
+
+impl Error for StepError {}
+
+/// Runs in place of the built-in behavior for a trap, see `LC3::set_trap_handler`. Handlers are
+/// `Send` so a machine with them can still be moved to another thread and run there, e.g. under a
+/// `RunControl`.
+pub type TrapHandler<I, M> = Box<dyn FnMut(&mut LC3<I, M>) + Send>;
+
+/// Runs before every instruction is fetched, see `LC3::on_instruction_boundary`
+pub type BoundaryHook<I, M> = Box<dyn FnMut(&mut LC3<I, M>) + Send>;
+
+pub struct LC3<I: Io = StdIo, M: MemoryBacking = Memory> {
+    pub memory: M,
     pub registers: [RegisterSize; REGISTER_COUNT],
     pub pc: u16,
     pub cond: CondFlag,
     pub running: bool,
+    /// The number of instructions executed
+    pub cycles: u64,
+    /// The number of cycles spent waiting on memory, see `memory_stall_cycles`
+    pub stall_cycles: u64,
+    /// How many extra cycles each memory access made by LD, LDR, ST and STR costs. LDI and STI
+    /// access memory twice so they cost double. Doesn't affect `cycles`, see `cycle_cost`.
+    pub memory_stall_cycles: u64,
+    /// The number of times the program has written to memory
+    pub memory_writes: u64,
+    /// Stop with `StopReason::WriteLimitExceeded` if the program tries to write to memory more
+    /// than this many times
+    pub write_limit: Option<u64>,
+    /// The number of JSR and JSRR calls that haven't returned with RET yet
+    pub call_depth: usize,
+    /// Stop with `StopReason::RecursionLimit` if a JSR or JSRR would make `call_depth` larger
+    /// than this
+    pub max_call_depth: Option<usize>,
+    pub decode_mode: DecodeMode,
+    /// Skip over words that don't decode instead of stopping, recording a
+    /// `Warning::SkippedWord` for each. Useful for running through a region of memory on a best
+    /// effort basis.
+    pub skip_decode_errors: bool,
+    /// Execute TRAPs with an unknown vector as no-ops, recording a `Warning::UnknownTrap` for
+    /// each, instead of stopping. Takes priority over `skip_decode_errors`.
+    pub ignore_unknown_traps: bool,
+    /// Execute the most common instructions without decoding them into an `Instruction` first
+    /// when nothing needs to inspect the decoded instruction. On by default.
+    pub fast_dispatch: bool,
+    /// Enables the nonstandard traps, see `TrapCode::is_extended`
+    pub extended_traps: bool,
+    /// Report the value of R0 when the program halts as its exit code, see `StopReason::Halted`
+    pub halt_exit_code: bool,
+    /// Warn when a subroutine call overwrites a return address that hasn't been used, see
+    /// `Warning::ClobberedReturnAddress`
+    pub lint_return_address: bool,
+    /// Warn when an instruction reads a register that no instruction, trap or call to
+    /// `set_register` has written, see `Warning::UninitializedRegister`. Assigning to `registers`
+    /// directly doesn't count as initializing a register. `AND Rx, Rx, #0` counts as a write
+    /// without a read since it's the usual way to clear a register.
+    pub lint_uninit_registers: bool,
+    /// Warn when the program writes to the trap or interrupt vector tables at 0x0000 to 0x01FF,
+    /// see `Warning::VectorTableWrite`
+    pub lint_vector_table_writes: bool,
+    /// Warn when the PC moves into the trap or interrupt vector tables, which usually means the
+    /// program ran away, see `Warning::ExecutingVectorTable`
+    pub lint_vector_table_execution: bool,
+    /// Stop with `StopReason::UnexpectedR7Write` when an instruction other than JSR or JSRR
+    /// writes R7, which usually means a return address is about to be lost
+    pub stop_on_r7_write: bool,
+    /// Clamp ADD results to 0x7FFF and 0x8000 on signed overflow instead of wrapping around.
+    /// Nonstandard, for courses that haven't covered two's complement overflow yet.
+    pub saturating_add: bool,
+    /// Stop with `StopReason::UninitRead` when LD, LDR or LDI read memory that the program never
+    /// wrote and that wasn't loaded with `load_words_at` or `load_image`. Memory passed to
+    /// `from_start_state` or written through `memory` directly counts as uninitialized.
+    pub trap_uninit_reads: bool,
+    /// Refuse to load programs with `load_words_at` or `load_additional` into the operating
+    /// system's memory below `PROGRAM_START` or the device registers from 0xFE00 on, so a user
+    /// program can't overwrite an OS loaded before it. Turn it on after loading the OS.
+    pub protect_system_memory: bool,
+    pub warnings: Vec<Warning>,
+    /// Count how often branches are taken, see `branch_stats`
+    pub collect_branch_stats: bool,
+    /// Keep track of the lowest value written to R6, the stack pointer by convention, see
+    /// `max_stack_usage`
+    pub track_stack_usage: bool,
+    /// Only flush output after writing a newline, before reading input and at HALT instead of
+    /// after every trap that writes output. Much faster for programs that print a lot but a
+    /// prompt without a newline won't show until the program reads input.
+    pub line_buffered_output: bool,
+    /// Replaces bytes written by OUT, PUTS and PUTSP, e.g. to make control characters visible
+    /// in logs. Bytes without an entry are written unchanged and bytes that map to `None` aren't
+    /// written at all.
+    pub output_map: HashMap<u8, Option<u8>>,
+    pub io: I,
+    stop_reason: Option<StopReason>,
+    return_address_pending: bool,
+    trap_handlers: HashMap<TrapCode, TrapHandler<I, M>>,
+    boundary_hook: Option<BoundaryHook<I, M>>,
+    run_control: Option<RunControl>,
+    branch_stats: BranchStats,
+    initialized: AddressSet,
+    /// One bit for each register that has been written
+    registers_set: u8,
+    /// Whether the last instruction fetched came from the vector tables
+    fetched_from_vector_table: bool,
+    trace_writer: Option<Box<dyn Write + Send>>,
+    /// The address and encoding of the last instruction executed
+    last_executed: Option<(MemoryLocationSize, InstructionSize)>,
+    lowest_stack_pointer: Option<RegisterSize>,
 }
 
 impl LC3 {
+    /// # Panics if `bytes` isn't a valid program, see `from_bytes`
     pub fn new(bytes: &[u8]) -> Self {
-        let origin_bytes: [u8; 2] = [bytes[0], bytes[1]];
-        let origin = u16::from_be_bytes(origin_bytes);
-
-        let mut memory = [0; MAX_MEMORY_SIZE];
-        for (index, slice) in bytes[2..].chunks(2).enumerate() {
-            let first = slice[0];
-            let second = slice.get(1).copied().unwrap_or(0);
-            let instruction = u16::from_be_bytes([first, second]);
-            memory[index * 2 + origin as usize] = instruction;
+        match Self::from_bytes(bytes) {
+            Ok(machine) => machine,
+            Err(e) => panic!("{}", e),
         }
+    }
+
+    /// Like `new` but every register starts out holding `init` instead of 0, e.g. a poison value
+    /// to make use of a register before it's set stand out. See also `lint_uninit_registers`.
+    ///
+    /// # Panics if `bytes` isn't a valid program, see `from_bytes`
+    pub fn new_with_reg_init(bytes: &[u8], init: RegisterSize) -> Self {
+        let mut machine = Self::new(bytes);
+        machine.registers = [init; REGISTER_COUNT];
+        machine
+    }
+
+    /// Creates a machine from a program in the LC3 object file format. The first word is the
+    /// origin the rest of the program is loaded at and the machine starts executing from.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, LoadError> {
+        let (origin, words) = parse_object(bytes)?;
+
+        let mut machine = Self::from_start_state([0; MAX_MEMORY_SIZE]);
+        machine.load_words_at(origin, &words)?;
+        machine.pc = origin;
+
+        Ok(machine)
+    }
 
+    pub fn from_start_state(memory: Memory) -> Self {
         LC3 {
             memory,
             registers: [0; REGISTER_COUNT],
-            pc: origin,
+            pc: PROGRAM_START,
             cond: CondFlag::ZERO,
             running: false,
+            cycles: 0,
+            stall_cycles: 0,
+            memory_stall_cycles: 0,
+            memory_writes: 0,
+            write_limit: None,
+            call_depth: 0,
+            max_call_depth: None,
+            decode_mode: DecodeMode::default(),
+            skip_decode_errors: false,
+            ignore_unknown_traps: false,
+            fast_dispatch: true,
+            extended_traps: false,
+            halt_exit_code: false,
+            lint_return_address: false,
+            lint_uninit_registers: false,
+            lint_vector_table_writes: false,
+            lint_vector_table_execution: false,
+            stop_on_r7_write: false,
+            saturating_add: false,
+            trap_uninit_reads: false,
+            protect_system_memory: false,
+            warnings: Vec::new(),
+            collect_branch_stats: false,
+            track_stack_usage: false,
+            line_buffered_output: false,
+            output_map: HashMap::new(),
+            io: StdIo,
+            stop_reason: None,
+            return_address_pending: false,
+            trap_handlers: HashMap::new(),
+            boundary_hook: None,
+            run_control: None,
+            branch_stats: BranchStats::default(),
+            initialized: AddressSet::new(),
+            registers_set: 0,
+            fetched_from_vector_table: false,
+            trace_writer: None,
+            last_executed: None,
+            lowest_stack_pointer: None,
         }
     }
+}
+
+impl<I: Io, M: MemoryBacking> LC3<I, M> {
+    /// Swap out where the machine's traps read input from and write output to
+    ///
+    /// Trap handlers and the instruction boundary hook are written for a particular `Io` so any
+    /// that were set are dropped.
+    pub fn with_io<J: Io>(self, io: J) -> LC3<J, M> {
+        self.rebuild(|_, memory| (io, memory))
+    }
 
-    pub fn from_start_state(memory: Memory) -> Self {
+    /// Swap out the storage backing the machine's memory. `memory` is used as is, nothing is
+    /// copied over from the current memory.
+    ///
+    /// Trap handlers and the instruction boundary hook are written for a particular
+    /// `MemoryBacking` so any that were set are dropped.
+    pub fn with_memory<N: MemoryBacking>(self, memory: N) -> LC3<I, N> {
+        self.rebuild(|io, _| (io, memory))
+    }
+
+    fn rebuild<J: Io, N: MemoryBacking>(self, parts: impl FnOnce(I, M) -> (J, N)) -> LC3<J, N> {
+        let (io, memory) = parts(self.io, self.memory);
         LC3 {
             memory,
-            registers: [0; REGISTER_COUNT],
-            pc: PROGRAM_START,
-            cond: CondFlag::ZERO,
-            running: false,
+            registers: self.registers,
+            pc: self.pc,
+            cond: self.cond,
+            running: self.running,
+            cycles: self.cycles,
+            stall_cycles: self.stall_cycles,
+            memory_stall_cycles: self.memory_stall_cycles,
+            memory_writes: self.memory_writes,
+            write_limit: self.write_limit,
+            call_depth: self.call_depth,
+            max_call_depth: self.max_call_depth,
+            decode_mode: self.decode_mode,
+            skip_decode_errors: self.skip_decode_errors,
+            ignore_unknown_traps: self.ignore_unknown_traps,
+            fast_dispatch: self.fast_dispatch,
+            extended_traps: self.extended_traps,
+            halt_exit_code: self.halt_exit_code,
+            lint_return_address: self.lint_return_address,
+            lint_uninit_registers: self.lint_uninit_registers,
+            lint_vector_table_writes: self.lint_vector_table_writes,
+            lint_vector_table_execution: self.lint_vector_table_execution,
+            stop_on_r7_write: self.stop_on_r7_write,
+            saturating_add: self.saturating_add,
+            trap_uninit_reads: self.trap_uninit_reads,
+            protect_system_memory: self.protect_system_memory,
+            warnings: self.warnings,
+            collect_branch_stats: self.collect_branch_stats,
+            track_stack_usage: self.track_stack_usage,
+            line_buffered_output: self.line_buffered_output,
+            output_map: self.output_map,
+            io,
+            stop_reason: self.stop_reason,
+            return_address_pending: self.return_address_pending,
+            trap_handlers: HashMap::new(),
+            boundary_hook: None,
+            run_control: self.run_control,
+            branch_stats: self.branch_stats,
+            initialized: self.initialized,
+            registers_set: self.registers_set,
+            fetched_from_vector_table: self.fetched_from_vector_table,
+            trace_writer: self.trace_writer,
+            last_executed: self.last_executed,
+            lowest_stack_pointer: self.lowest_stack_pointer,
+        }
+    }
+
+    /// Copies `words` into memory starting at `origin`
+    pub fn load_words_at(
+        &mut self,
+        origin: MemoryLocationSize,
+        words: &[u16],
+    ) -> Result<(), LoadError> {
+        let end = origin as usize + words.len();
+        if end > MAX_MEMORY_SIZE {
+            return Err(LoadError::TooLarge {
+                origin,
+                len: words.len(),
+            });
+        }
+        let overlaps_system = origin < PROGRAM_START || end > DEVICE_REGISTERS_START as usize;
+        if self.protect_system_memory && !words.is_empty() && overlaps_system {
+            return Err(LoadError::ReservedRegion {
+                origin,
+                len: words.len(),
+            });
+        }
+
+        for (address, &word) in (origin..=MemoryLocationSize::MAX).zip(words) {
+            self.memory.write(address, word);
+            self.initialized.insert(address);
+        }
+        Ok(())
+    }
+
+    /// Loads another program in the LC3 object file format at its own origin without touching
+    /// the rest of memory or the PC, e.g. to load an OS alongside a user program
+    pub fn load_additional(&mut self, bytes: &[u8]) -> Result<(), LoadError> {
+        let (origin, words) = parse_object(bytes)?;
+        self.load_words_at(origin, &words)
+    }
+
+    /// Returns a copy of all of memory
+    pub fn memory_image(&self) -> Vec<MemoryLocationSize> {
+        (0..=MemoryLocationSize::MAX)
+            .map(|address| self.memory.read(address))
+            .collect()
+    }
+
+    /// Calls `f` with a copy of all of memory and writes back the words it changed. Changed words
+    /// count as initialized, see `trap_uninit_reads`.
+    pub fn with_memory_mut<F: FnOnce(&mut [MemoryLocationSize])>(&mut self, f: F) {
+        let original = self.memory_image();
+        let mut image = original.clone();
+        f(&mut image);
+        for (address, (&old, &new)) in original.iter().zip(&image).enumerate() {
+            if old != new {
+                self.memory.write(address as MemoryLocationSize, new);
+                self.initialized.insert(address as MemoryLocationSize);
+            }
+        }
+    }
+
+    /// Calls `f` with the registers. Registers it changes count as initialized, see
+    /// `lint_uninit_registers`. The cond register isn't updated.
+    pub fn with_registers_mut<F: FnOnce(&mut [RegisterSize; REGISTER_COUNT])>(&mut self, f: F) {
+        let original = self.registers;
+        f(&mut self.registers);
+        for (register, (old, new)) in original.iter().zip(&self.registers).enumerate() {
+            if old != new {
+                self.registers_set |= 1 << register;
+            }
+        }
+    }
+
+    /// Returns the words in `range` as an LC3 object file with `range.start` as its origin, the
+    /// format `from_bytes` and `load_additional` read
+    pub fn to_obj(&self, range: Range<MemoryLocationSize>) -> Vec<u8> {
+        let mut bytes = range.start.to_be_bytes().to_vec();
+        for address in range {
+            bytes.extend_from_slice(&self.memory.read(address).to_be_bytes());
+        }
+        bytes
+    }
+
+    /// Returns a line for each address in `range` showing the word there as unsigned hex, signed
+    /// decimal and, if it's a printable ASCII character, the character:
+    ///
+    /// ```text
+    /// x3000  x0041      65  A
+    /// x3001  xFFFF      -1
+    /// ```
+    pub fn dump_memory_annotated(&self, range: Range<MemoryLocationSize>) -> String {
+        let mut dump = String::new();
+        for address in range {
+            let word = self.memory.read(address);
+            let ch = match u8::try_from(word) {
+                Ok(byte) if byte.is_ascii_graphic() || byte == b' ' => byte as char,
+                _ => ' ',
+            };
+            let line = format!(
+                "x{:04X}  x{:04X}  {:>6}  {}",
+                address, word, word as i16, ch
+            );
+            dump.push_str(line.trim_end());
+            dump.push('\n');
+        }
+        dump
+    }
+
+    /// Overwrites memory starting at address 0 with `image`. Anything in `image` past the end of
+    /// memory is ignored.
+    pub fn load_image(&mut self, image: &[MemoryLocationSize]) {
+        for (address, &word) in (0..=MemoryLocationSize::MAX).zip(image) {
+            self.memory.write(address, word);
+            self.initialized.insert(address);
+        }
+    }
+
+    /// Returns the address a jump table dispatch would jump to: the entry at `index` of the table
+    /// of addresses starting at `table_base`.
+    ///
+    /// LC3 has no indirect jump so programs dispatch through a table by loading the entry into a
+    /// register and jumping through it:
+    ///
+    /// ```text
+    /// ADD R1, R1, R0 ; R1 holds the table base, R0 the index
+    /// LDR R2, R1, #0 ; R2 = table[index]
+    /// JMP R2
+    /// ```
+    pub fn compute_jump_target(
+        &self,
+        table_base: MemoryLocationSize,
+        index: u16,
+    ) -> MemoryLocationSize {
+        self.memory.read(table_base.wrapping_add(index))
+    }
+
+    /// Executes the instruction at the PC and returns it. If the instruction can't be executed it
+    /// has no effect and the PC is put back, except that a word that doesn't decode is skipped
+    /// when `skip_decode_errors` is on. What happens before decoding still happens either way: the
+    /// boundary hook runs, the word is read from memory and `lint_vector_table_execution` may
+    /// warn.
+    pub fn step(&mut self) -> Result<Instruction, StepError> {
+        self.instruction_boundary();
+        let pc = self.pc;
+        let cycle = self.cycles;
+        let raw_instr = self.fetch();
+        let result = self
+            .decode(raw_instr)
+            .map_err(StepError::from)
+            .and_then(|instr| {
+                self.execute(instr)?;
+                Ok(instr)
+            });
+
+        match result {
+            Ok(_) => self.finish_step(cycle, pc, raw_instr),
+            Err(StepError::Decode(ref e)) if self.recover_decode_error(e, pc, raw_instr) => {}
+            Err(_) => self.pc = pc,
+        }
+        result
+    }
+
+    /// Writes `instr` into memory at the PC and executes it, leaving the PC after it. Handy for
+    /// trying out a single instruction without setting up a program.
+    ///
+    /// # Panics if the instruction can't be executed
+    pub fn exec_instruction(&mut self, instr: Instruction) {
+        self.memory.write(self.pc, u16::from_be(instr.encode()));
+        self.initialized.insert(self.pc);
+        if let Err(e) = self.step() {
+            panic!("{}", e);
+        }
+    }
+
+    /// Reads the instruction at the PC and moves the PC past it
+    pub fn fetch(&mut self) -> InstructionSize {
+        if self.lint_vector_table_execution {
+            let in_table = self.pc <= VECTOR_TABLE_END;
+            if in_table && !self.fetched_from_vector_table {
+                self.warnings.push(Warning::ExecutingVectorTable(self.pc));
+            }
+            self.fetched_from_vector_table = in_table;
+        }
+
+        let raw_instr = self.memory.read(self.pc);
+        self.pc = self.pc.wrapping_add(1);
+        raw_instr
+    }
+
+    /// Decodes `raw_instr` using the machine's `decode_mode`
+    pub fn decode(&self, raw_instr: InstructionSize) -> Result<Instruction, DecodeError> {
+        Instruction::try_decode(raw_instr, self.decode_mode)
+    }
+
+    /// Executes `instr` as the instruction that was just fetched, so the PC should already point
+    /// past it. If the instruction can't be executed an error is returned before anything changes.
+    pub fn execute(&mut self, instr: Instruction) -> Result<(), StepError> {
+        self.check_executable(instr)?;
+        self.cycles += 1;
+
+        if self.lint_return_address {
+            self.check_return_address(instr, self.pc.wrapping_sub(1));
+        }
+        if self.lint_uninit_registers {
+            self.check_uninit_registers(instr, self.pc.wrapping_sub(1));
         }
+
+        self.dispatch(instr);
+        Ok(())
     }
 
-    pub fn step(&mut self) {
-        let raw_instr = self.memory[self.pc as usize];
-        self.pc += 1;
-        let instr = Instruction::decode(raw_instr);
+    /// Returns an error if `instr` can't be executed by this machine even though it decoded
+    fn check_executable(&self, instr: Instruction) -> Result<(), StepError> {
+        match instr {
+            Instruction::Trap(trap)
+                if trap.vect8.is_extended()
+                    && !self.extended_traps
+                    && !self.trap_handlers.contains_key(&trap.vect8) =>
+            {
+                Err(StepError::ExtendedTrapDisabled(trap.vect8))
+            }
+            _ => Ok(()),
+        }
+    }
 
+    /// Runs the handler for `instr`
+    fn dispatch(&mut self, instr: Instruction) {
         match instr {
             Instruction::AddImmediate(instr) => self.add_immediate(instr),
             Instruction::AddRegister(instr) => self.add_register(instr),
@@ -95,140 +708,503 @@ impl LC3 {
         }
     }
 
+    /// Executes the instruction at the PC like `step` but without returning it, which lets the
+    /// most common instructions skip decoding when fast dispatch is on.
+    ///
+    /// # Panics if the instruction can't be executed and isn't skipped by `skip_decode_errors`
+    pub fn step_unchecked(&mut self) {
+        if let Err(e) = self.try_step_unchecked() {
+            panic!("{}", e);
+        }
+    }
+
+    /// Does the work of `step_unchecked`, returning the error instead of panicking if the
+    /// instruction can't be executed. The PC is left at the instruction when it fails.
+    fn try_step_unchecked(&mut self) -> Result<(), StepError> {
+        self.instruction_boundary();
+        let pc = self.pc;
+        let cycle = self.cycles;
+        let raw_instr = self.fetch();
+        if !(self.can_dispatch_fast() && self.step_fast(raw_instr)) {
+            match self
+                .decode(raw_instr)
+                .map_err(StepError::from)
+                .and_then(|instr| self.execute(instr))
+            {
+                Ok(()) => {}
+                Err(StepError::Decode(ref e)) if self.recover_decode_error(e, pc, raw_instr) => {
+                    return Ok(());
+                }
+                Err(e) => {
+                    self.pc = pc;
+                    return Err(e);
+                }
+            }
+        }
+
+        self.finish_step(cycle, pc, raw_instr);
+        Ok(())
+    }
+
+    /// Calls `hook` between instructions, before the next one is fetched, e.g. to feed the program
+    /// input that arrived while it was running. The hook is free to change the PC.
+    pub fn on_instruction_boundary(&mut self, hook: BoundaryHook<I, M>) {
+        self.boundary_hook = Some(hook);
+    }
+
+    fn instruction_boundary(&mut self) {
+        // The hook is taken out while it runs so it can borrow the machine mutably
+        if let Some(mut hook) = self.boundary_hook.take() {
+            hook(self);
+            self.boundary_hook.get_or_insert(hook);
+        }
+    }
+
+    /// Carries on past a word that didn't decode when `ignore_unknown_traps` or
+    /// `skip_decode_errors` allow it, leaving the PC after the word. Returns false if the error
+    /// should stop the machine.
+    fn recover_decode_error(
+        &mut self,
+        error: &DecodeError,
+        pc: MemoryLocationSize,
+        raw_instr: InstructionSize,
+    ) -> bool {
+        match error {
+            DecodeError::UnknownTrap(_) if self.ignore_unknown_traps => {
+                self.cycles += 1;
+                self.warnings
+                    .push(Warning::UnknownTrap(pc, raw_instr as u8));
+                true
+            }
+            _ if self.skip_decode_errors => {
+                self.warnings.push(Warning::SkippedWord(pc, raw_instr));
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Records an instruction that was executed successfully
+    fn finish_step(&mut self, cycle: u64, pc: MemoryLocationSize, raw_instr: InstructionSize) {
+        self.last_executed = Some((pc, raw_instr));
+        self.write_trace(cycle, pc, raw_instr);
+    }
+
+    /// Returns the address the most recently executed instruction was fetched from
+    pub fn last_pc(&self) -> Option<MemoryLocationSize> {
+        self.last_executed.map(|(pc, _)| pc)
+    }
+
+    /// Returns the most recently executed instruction
+    pub fn last_instruction(&self) -> Option<Instruction> {
+        let (_, raw_instr) = self.last_executed?;
+        self.decode(raw_instr).ok()
+    }
+
+    /// Whether `step_fast` can be used, which is only when nothing needs to see the decoded
+    /// instruction and nothing needs to be validated
+    fn can_dispatch_fast(&self) -> bool {
+        self.fast_dispatch
+            && self.decode_mode == DecodeMode::Lenient
+            && !self.lint_return_address
+            && !self.lint_uninit_registers
+            && !self.collect_branch_stats
+            && !self.trap_uninit_reads
+    }
+
+    /// Executes ADD, AND, BR and LD straight from their encoding, since they make up most of a
+    /// typical hot loop, without building an `Instruction`. Returns false without doing anything
+    /// for any other instruction.
+    fn step_fast(&mut self, instr: InstructionSize) -> bool {
+        let opcode = OpCode::from_instruction(instr);
+        match opcode {
+            OpCode::Add | OpCode::And | OpCode::Branch | OpCode::Load => {}
+            _ => return false,
+        }
+
+        self.cycles += 1;
+
+        match opcode {
+            OpCode::Add | OpCode::And => {
+                let sr1 = self.registers[get_sr1(instr) as usize];
+                let operand = if get_immediate_mode(instr) == 1 {
+                    get_imm5(instr)
+                } else {
+                    self.registers[get_sr2(instr) as usize]
+                };
+
+                let value = if opcode == OpCode::Add {
+                    self.add(sr1, operand)
+                } else {
+                    sr1 & operand
+                };
+                self.write_result(get_dr(instr), value);
+            }
+            OpCode::Branch => {
+                if (get_nzp(instr) & self.cond).bits() > 0 {
+                    self.pc = self.pc.wrapping_add(get_pc_offset9(instr));
+                }
+            }
+            OpCode::Load => {
+                self.stall(1);
+                let address = self.pc.wrapping_add(get_pc_offset9(instr));
+                self.write_result(get_dr(instr), self.memory.read(address));
+            }
+            _ => unreachable!(),
+        }
+
+        true
+    }
+
     pub fn add_immediate(&mut self, instr: AddImmediate) {
-        // u32s are added to prevent overflow
-        let value: u32 = self.registers[instr.sr1 as usize] as u32 + (instr.imm5 as u16) as u32;
-        self.set_register(instr.dr, value as u16)
+        let value = self.add(self.registers[instr.sr1 as usize], instr.imm5);
+        self.write_result(instr.dr, value)
     }
 
     pub fn add_register(&mut self, instr: AddRegister) {
-        // u32s are added to prevent overflow
-        let value: u32 =
-            self.registers[instr.sr1 as usize] as u32 + self.registers[instr.sr2 as usize] as u32;
-        self.set_register(instr.dr, value as u16)
+        let value = self.add(
+            self.registers[instr.sr1 as usize],
+            self.registers[instr.sr2 as usize],
+        );
+        self.write_result(instr.dr, value)
+    }
+
+    /// Adds two register values the way ADD does, see `saturating_add`
+    fn add(&self, a: RegisterSize, b: RegisterSize) -> RegisterSize {
+        if self.saturating_add {
+            (a as i16).saturating_add(b as i16) as RegisterSize
+        } else {
+            a.wrapping_add(b)
+        }
     }
 
     pub fn and_immediate(&mut self, instr: AndImmediate) {
-        let value = self.registers[instr.sr1 as usize] & (instr.imm5 as u16);
-        self.set_register(instr.dr, value as u16)
+        let value = self.registers[instr.sr1 as usize] & instr.imm5;
+        self.write_result(instr.dr, value)
     }
 
     pub fn and_register(&mut self, instr: AndRegister) {
         let value = self.registers[instr.sr1 as usize] & self.registers[instr.sr2 as usize];
-        self.set_register(instr.dr, value)
+        self.write_result(instr.dr, value)
     }
 
     pub fn branch(&mut self, instr: Branch) {
-        if (instr.nzp & self.cond).bits() > 0 {
-            self.pc += instr.pc_offset9;
+        let taken = (instr.nzp & self.cond).bits() > 0;
+        if self.collect_branch_stats {
+            self.branch_stats.record(self.pc.wrapping_sub(1), taken);
+        }
+
+        if taken {
+            self.pc = self.pc.wrapping_add(instr.pc_offset9);
         }
     }
 
     pub fn jump(&mut self, instr: Jump) {
+        if instr.base_r == 7 {
+            self.call_depth = self.call_depth.saturating_sub(1);
+        }
         self.pc = self.registers[instr.base_r as usize];
     }
 
     pub fn jump_subroutine_offset(&mut self, instr: JumpSubRoutineOffset) {
-        self.registers[7] = self.pc;
-        self.pc += instr.pc_offset11;
+        if !self.enter_subroutine() {
+            return;
+        }
+        self.put_register(7, self.pc);
+        self.pc = self.pc.wrapping_add(instr.pc_offset11);
     }
 
     pub fn jump_subroutine_register(&mut self, instr: JumpSubRoutineRegister) {
-        self.registers[7] = self.pc;
+        if !self.enter_subroutine() {
+            return;
+        }
+        self.put_register(7, self.pc);
         self.pc = self.registers[instr.base_r as usize];
     }
 
     pub fn load(&mut self, instr: Load) {
-        let address = self.pc + instr.pc_offset9;
-        self.set_register(instr.dr, self.memory[address as usize]);
+        self.stall(1);
+        let address = self.pc.wrapping_add(instr.pc_offset9);
+        if let Some(value) = self.read_data(address) {
+            self.write_result(instr.dr, value);
+        }
     }
 
     pub fn load_base_offset(&mut self, instr: LoadBaseOffset) {
-        let address = self.registers[instr.base_r as usize] + instr.pc_offset6 as u16;
-        self.set_register(instr.dr, self.memory[address as usize]);
+        self.stall(1);
+        let address = self.registers[instr.base_r as usize].wrapping_add(instr.pc_offset6);
+        if let Some(value) = self.read_data(address) {
+            self.write_result(instr.dr, value);
+        }
     }
 
     pub fn load_effective_address(&mut self, instr: LoadEffectiveAddress) {
-        let address = self.pc + instr.pc_offset9;
-        self.set_register(instr.dr, address)
+        let address = self.pc.wrapping_add(instr.pc_offset9);
+        self.write_result(instr.dr, address)
     }
 
     pub fn load_indirect(&mut self, instr: LoadIndirect) {
-        let address = self.memory[(self.pc + instr.pc_offset9) as usize];
-        self.set_register(instr.dr, self.memory[address as usize]);
+        self.stall(2);
+        let address = match self.read_data(self.pc.wrapping_add(instr.pc_offset9)) {
+            Some(address) => address,
+            None => return,
+        };
+        if let Some(value) = self.read_data(address) {
+            self.write_result(instr.dr, value);
+        }
+    }
+
+    /// Charges the stall cycles for an instruction making `accesses` memory accesses
+    fn stall(&mut self, accesses: u64) {
+        self.stall_cycles += accesses * self.memory_stall_cycles;
+    }
+
+    /// Reads memory on behalf of a load. Returns `None` and stops the machine if the address is
+    /// uninitialized and `trap_uninit_reads` is on.
+    fn read_data(&mut self, address: MemoryLocationSize) -> Option<MemoryLocationSize> {
+        if self.trap_uninit_reads && !self.initialized.contains(address) {
+            self.stop(StopReason::UninitRead(address));
+            return None;
+        }
+        Some(self.memory.read(address))
     }
 
     pub fn not(&mut self, instr: Not) {
         let val = !self.registers[instr.sr1 as usize];
-        self.set_register(instr.dr, val);
+        self.write_result(instr.dr, val);
     }
 
     pub fn store(&mut self, instr: Store) {
-        let address = self.pc + instr.pc_offset9;
-        self.memory[address as usize] = self.registers[instr.sr as usize];
+        self.stall(1);
+        let address = self.pc.wrapping_add(instr.pc_offset9);
+        self.write_memory(address, self.registers[instr.sr as usize]);
     }
 
     pub fn store_base_offset(&mut self, instr: StoreBaseOffset) {
-        let address = self.registers[instr.base_r as usize] + instr.pc_offset6 as u16;
-        self.memory[address as usize] = self.registers[instr.sr as usize];
+        self.stall(1);
+        let address = self.registers[instr.base_r as usize].wrapping_add(instr.pc_offset6);
+        self.write_memory(address, self.registers[instr.sr as usize]);
     }
 
     pub fn store_indirect(&mut self, instr: StoreIndirect) {
-        let indirect_address = self.pc + instr.pc_offset9;
-        let address = self.memory[indirect_address as usize];
-        self.memory[address as usize] = self.registers[instr.sr as usize];
+        self.stall(2);
+        let indirect_address = self.pc.wrapping_add(instr.pc_offset9);
+        let address = self.memory.read(indirect_address);
+        self.write_memory(address, self.registers[instr.sr as usize]);
+    }
+
+    /// Writes to memory on behalf of the program, counting the write against `write_limit`.
+    /// Returns false and stops the machine instead of writing if the limit has been reached.
+    fn write_memory(&mut self, address: MemoryLocationSize, value: MemoryLocationSize) -> bool {
+        if let Some(limit) = self.write_limit {
+            if self.memory_writes >= limit {
+                self.stop(StopReason::WriteLimitExceeded);
+                return false;
+            }
+        }
+
+        if self.lint_vector_table_writes && address <= VECTOR_TABLE_END {
+            self.warnings.push(Warning::VectorTableWrite(address));
+        }
+
+        self.memory_writes += 1;
+        self.memory.write(address, value);
+        self.initialized.insert(address);
+        if address == MCR_ADDRESS && value & 0x8000 == 0 {
+            self.halt();
+        }
+        true
+    }
+
+    /// Stops the machine because the program halted
+    fn halt(&mut self) {
+        let code = if self.halt_exit_code {
+            self.registers[0]
+        } else {
+            0
+        };
+        self.stop(StopReason::Halted { code });
     }
 
+    /// Use `handler` instead of the built-in behavior whenever the program executes the `code`
+    /// trap. Extended traps with a handler run even if `extended_traps` is off.
+    pub fn set_trap_handler(&mut self, code: TrapCode, handler: TrapHandler<I, M>) {
+        self.trap_handlers.insert(code, handler);
+    }
+
+    /// # Panics if `instr` is an extended trap without a handler and extended traps are disabled
     pub fn trap(&mut self, instr: Trap) {
+        // The handler is taken out while it runs so it can borrow the machine mutably
+        if let Some(mut handler) = self.trap_handlers.remove(&instr.vect8) {
+            handler(self);
+            self.trap_handlers.entry(instr.vect8).or_insert(handler);
+            return;
+        }
+
+        if instr.vect8.is_extended() && !self.extended_traps {
+            panic!(
+                "{:?} is an extended trap but extended traps are disabled",
+                instr.vect8
+            );
+        }
+
         match instr.vect8 {
             TrapCode::GetC => {
-                let ch = read_char();
-                self.registers[0] = ch as u16;
+                self.flush_before_input();
+                match self.io.read_byte() {
+                    Some(ch) => self.put_register(0, ch as u16),
+                    None => self.stop(StopReason::InputExhausted),
+                }
             }
             TrapCode::Halt => {
-                println!("HALT");
-                self.running = false;
+                self.io.write_bytes(b"HALT\n");
+                self.io.flush();
+                self.halt();
             }
             TrapCode::In => {
-                print!("Enter a character: ");
-                let ch = read_char();
-                flush_or_fail();
-                self.registers[0] = ch as u16;
+                self.io.write_bytes(b"Enter a character: ");
+                self.io.flush();
+                match self.io.read_byte() {
+                    Some(ch) => self.put_register(0, ch as u16),
+                    None => self.stop(StopReason::InputExhausted),
+                }
             }
             TrapCode::Out => {
-                let c = self.registers[0];
-                print!("{}", c);
-                flush_or_fail();
+                let c = self.registers[0] as u8;
+                self.write_char(c);
+                self.flush_output(c == b'\n');
             }
             TrapCode::Puts => {
-                let mut starting_address = self.registers[0] as usize;
-                let mut ch = self.memory[starting_address];
+                let mut starting_address = self.registers[0];
+                let mut ch = self.memory.read(starting_address);
+                let mut wrote_newline = false;
                 while ch != 0 {
-                    print!("{}", ch as u8 as char);
-                    starting_address += 1;
-                    ch = self.memory[starting_address];
+                    self.write_char(ch as u8);
+                    wrote_newline |= ch as u8 == b'\n';
+                    starting_address = starting_address.wrapping_add(1);
+                    ch = self.memory.read(starting_address);
                 }
-                flush_or_fail();
+                self.flush_output(wrote_newline);
             }
             TrapCode::PutsP => {
-                let mut starting_address = self.registers[0] as usize;
-                let mut ch = self.memory[starting_address];
-                while ch != 0 {
-                    let bytes = self.memory[starting_address].to_be_bytes();
-                    print!("{}", bytes[0]);
-                    if bytes[1] == 0 {
-                        break;
+                // Each word packs two characters, the first in the low byte. A string with an odd
+                // length ends with a word whose high byte is null.
+                let mut address = self.registers[0];
+                let mut wrote_newline = false;
+                'string: loop {
+                    let [high, low] = self.memory.read(address).to_be_bytes();
+                    for &ch in &[low, high] {
+                        if ch == 0 {
+                            break 'string;
+                        }
+                        self.write_char(ch);
+                        wrote_newline |= ch == b'\n';
+                    }
+                    address = address.wrapping_add(1);
+                }
+                self.flush_output(wrote_newline);
+            }
+            TrapCode::Gets => {
+                self.flush_before_input();
+                let mut address = self.registers[0];
+                let mut count = 0;
+                loop {
+                    match self.io.read_byte() {
+                        None if count == 0 => return self.stop(StopReason::InputExhausted),
+                        None | Some(b'\n') => break,
+                        Some(ch) => {
+                            if !self.write_memory(address, ch as u16) {
+                                return;
+                            }
+                            address = address.wrapping_add(1);
+                            count += 1;
+                        }
                     }
-                    print!("{}", bytes[1]);
+                }
+                if !self.write_memory(address, 0) {
+                    return;
+                }
+                self.put_register(1, count);
+            }
+            TrapCode::PutN => {
+                let value = self.registers[0] as i16;
+                self.io.write_bytes(value.to_string().as_bytes());
+                self.flush_output(false);
+            }
+            TrapCode::ClrScr => {
+                self.io.write_bytes(ANSI_CLEAR_SCREEN);
+                self.flush_output(false);
+            }
+            TrapCode::Flush => self.io.flush(),
+        }
+    }
+
+    /// Writes a character of a program's output after translating it with `output_map`
+    fn write_char(&mut self, ch: u8) {
+        match self.output_map.get(&ch) {
+            Some(Some(mapped)) => self.io.write_bytes(&[*mapped]),
+            Some(None) => {}
+            None => self.io.write_bytes(&[ch]),
+        }
+    }
+
+    /// Flushes output written by a trap unless `line_buffered_output` is on and it didn't include a
+    /// newline
+    fn flush_output(&mut self, wrote_newline: bool) {
+        if !self.line_buffered_output || wrote_newline {
+            self.io.flush();
+        }
+    }
+
+    /// Makes sure a prompt is visible before the program waits for input. Output is already
+    /// flushed when it isn't line buffered.
+    fn flush_before_input(&mut self) {
+        if self.line_buffered_output {
+            self.io.flush();
+        }
+    }
 
-                    starting_address += 1;
-                    ch = self.memory[starting_address];
+    /// A return address is pending from the time a JSR/JSRR puts it in R7 until R7 is read, either
+    /// by a RET or to save it somewhere else. TRAP doesn't use R7 since traps are handled outside
+    /// of the machine.
+    fn check_return_address(&mut self, instr: Instruction, pc: MemoryLocationSize) {
+        match instr {
+            Instruction::JumpSubRoutineOffset(_) | Instruction::JumpSubRoutineRegister(_) => {
+                if self.return_address_pending {
+                    self.warnings.push(Warning::ClobberedReturnAddress(pc));
                 }
-                flush_or_fail();
+                self.return_address_pending = true;
+            }
+            instr if instr.source_registers().contains(&7) => {
+                self.return_address_pending = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn check_uninit_registers(&mut self, instr: Instruction, pc: MemoryLocationSize) {
+        let sources = match instr {
+            // AND Rx, Rx, #0 is how registers are cleared so it only counts as a write
+            Instruction::AndImmediate(and) if and.imm5 == 0 => vec![],
+            _ => instr.source_registers(),
+        };
+        for register in sources {
+            if self.registers_set & (1 << register) == 0 {
+                self.warnings
+                    .push(Warning::UninitializedRegister(pc, register));
             }
         }
     }
 
+    /// Writes the result of an instruction to `register` like `set_register`. Writing R7 stops the
+    /// machine instead when `stop_on_r7_write` is on, since only JSR and JSRR should.
+    fn write_result(&mut self, register: RegisterIndex, value: RegisterSize) {
+        if register == 7 && self.stop_on_r7_write {
+            return self.stop(StopReason::UnexpectedR7Write(self.pc.wrapping_sub(1)));
+        }
+        self.set_register(register, value);
+    }
+
     /// Put `value` in `register` and set the cond register based on `value`
     pub fn set_register(&mut self, register: RegisterIndex, value: RegisterSize) {
         self.cond = match value {
@@ -237,56 +1213,332 @@ impl LC3 {
             _ => CondFlag::POSITIVE,
         };
 
+        self.put_register(register, value);
+    }
+
+    /// Put `value` in `register` without touching the cond register
+    fn put_register(&mut self, register: RegisterIndex, value: RegisterSize) {
         self.registers[register as usize] = value;
+        self.registers_set |= 1 << register;
+        if register == 6 && self.track_stack_usage {
+            self.lowest_stack_pointer =
+                Some(self.lowest_stack_pointer.map_or(value, |v| v.min(value)));
+        }
     }
 
-    pub fn run(&mut self) {
-        self.running = true;
-        while self.running {
-            self.step()
+    /// Execute up to `n` instructions, stopping early if the program halts.
+    ///
+    /// Returns the reason the machine stopped, or `None` if all `n` instructions were executed and
+    /// the program is still running.
+    pub fn step_n(&mut self, n: usize) -> Option<StopReason> {
+        self.start();
+        for _ in 0..n {
+            self.step_unchecked();
+            if !self.running {
+                return Some(self.stop_reason());
+            }
         }
+
+        None
     }
-}
 
-fn read_char() -> u8 {
-    io::stdin()
-        .bytes()
-        .nth(0)
-        .expect("Couldn't get char")
-        .expect("Couldn't get char")
-}
+    /// Like `step_n` but stops with `StopReason::Fault` instead of panicking when an instruction
+    /// can't be executed, for running programs that might not be well formed
+    pub fn try_step_n(&mut self, n: usize) -> Option<StopReason> {
+        self.start();
+        for _ in 0..n {
+            if let Err(e) = self.try_step_unchecked() {
+                self.stop(StopReason::Fault(self.pc, e));
+            }
+            if !self.running {
+                return Some(self.stop_reason());
+            }
+        }
 
-fn flush_or_fail() {
-    io::stdout().flush().expect("Flush failed");
-}
+        None
+    }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Runs until `register` holds `value`, the program stops or `max_cycles` instructions have
+    /// been executed, whichever comes first. The register is checked before each instruction, so
+    /// nothing is executed if it already holds `value`.
+    pub fn run_until_register(
+        &mut self,
+        register: RegisterIndex,
+        value: RegisterSize,
+        max_cycles: u64,
+    ) -> StopReason {
+        self.start();
+        for _ in 0..max_cycles {
+            if self.registers[register as usize] == value {
+                return StopReason::RegisterMatched;
+            }
+            self.step_unchecked();
+            if !self.running {
+                return self.stop_reason();
+            }
+        }
 
-    #[test]
-    fn add_register() {
-        let mut memory = [0; MAX_MEMORY_SIZE];
-        let dr = 1;
-        let sr1 = 2;
-        let sr2 = 3;
+        if self.registers[register as usize] == value {
+            StopReason::RegisterMatched
+        } else {
+            StopReason::CycleLimit
+        }
+    }
 
-        let instruction =
-            u16::from_be(Instruction::AddRegister(AddRegister { dr, sr1, sr2 }).encode());
+    /// Runs like `run` but stops with `StopReason::Timeout` once `timeout` has passed. The clock
+    /// is only checked every `TIMEOUT_CHECK_INTERVAL` instructions and a trap waiting for input
+    /// isn't interrupted, so the machine can run a little past the timeout.
+    pub fn run_with_timeout(&mut self, timeout: Duration) -> StopReason {
+        let started = Instant::now();
+        self.start();
+        while self.running {
+            for _ in 0..TIMEOUT_CHECK_INTERVAL {
+                if !self.running {
+                    break;
+                }
+                self.step_unchecked();
+            }
+            if self.running && started.elapsed() >= timeout {
+                self.stop(StopReason::Timeout);
+            }
+        }
 
-        memory[PROGRAM_START as usize] = instruction;
+        self.stop_reason()
+    }
 
-        let mut machine = LC3::from_start_state(memory);
-        machine.registers[sr1 as usize] = 5;
-        machine.registers[sr2 as usize] = 6;
-        machine.step();
+    pub fn run(&mut self) -> StopReason {
+        self.start();
+        while self.running {
+            self.check_run_control();
+            if !self.running {
+                break;
+            }
+            self.step_unchecked()
+        }
 
-        assert_eq!(machine.registers[dr as usize], 11);
-        assert_eq!(machine.cond, CondFlag::POSITIVE);
+        self.stop_reason()
     }
 
-    #[test]
-    fn add_immediate() {
+    /// Runs like `run` but with none of the per-instruction bookkeeping, for when throughput
+    /// matters more than debugging. Uses fast dispatch unless decoding is strict and skips
+    /// tracing, lints, branch statistics, `skip_decode_errors`, the instruction boundary hook and
+    /// tracking the last instruction, so only use it when none of those are turned on.
+    /// `trap_uninit_reads` is only partially applied.
+    ///
+    /// # Panics if an instruction can't be executed
+    pub fn run_fast(&mut self) -> StopReason {
+        // The fast path doesn't validate the unused bits strict decoding rejects
+        let lenient = self.decode_mode == DecodeMode::Lenient;
+        self.start();
+        while self.running {
+            let raw_instr = self.memory.read(self.pc);
+            self.pc = self.pc.wrapping_add(1);
+            if lenient && self.step_fast(raw_instr) {
+                continue;
+            }
+
+            let instr = self
+                .decode(raw_instr)
+                .map_err(StepError::from)
+                .and_then(|instr| self.check_executable(instr).map(|_| instr));
+            match instr {
+                Ok(instr) => {
+                    self.cycles += 1;
+                    self.dispatch(instr);
+                }
+                Err(e) => panic!("{}", e),
+            }
+        }
+
+        self.stop_reason()
+    }
+
+    /// Runs starting from `entry` instead of the current PC, e.g. to call a subroutine directly.
+    /// Point R7 at a HALT first if the subroutine should stop the machine when it returns.
+    pub fn run_from(&mut self, entry: MemoryLocationSize) -> StopReason {
+        self.pc = entry;
+        self.run()
+    }
+
+    /// Returns a closure that executes `GENERATOR_SLICE` instructions each time it is called and
+    /// then yields, so a front-end that can't block (e.g. one driven by requestAnimationFrame) can
+    /// pump the machine.
+    ///
+    /// The closure returns `None` while the program is still running. Once the program stops it
+    /// returns the `StopReason` on every subsequent call without executing anything else.
+    pub fn run_generator(&mut self) -> impl FnMut() -> Option<StopReason> + '_ {
+        let mut stopped = None;
+        move || {
+            if stopped.is_none() {
+                stopped = self.step_n(GENERATOR_SLICE);
+            }
+            stopped
+        }
+    }
+
+    /// Stops the machine after the current instruction, making `run` return
+    /// `StopReason::Stopped`. Use it to stop a program from a trap handler or between steps.
+    pub fn request_stop(&mut self) {
+        self.stop(StopReason::Stopped);
+    }
+
+    fn start(&mut self) {
+        self.running = true;
+        self.stop_reason = None;
+    }
+
+    /// Counts a subroutine call, or stops the machine if it would go over `max_call_depth`.
+    /// Returns whether the call should be made.
+    fn enter_subroutine(&mut self) -> bool {
+        if let Some(limit) = self.max_call_depth {
+            if self.call_depth >= limit {
+                self.stop(StopReason::RecursionLimit);
+                return false;
+            }
+        }
+        self.call_depth += 1;
+        true
+    }
+
+    fn stop(&mut self, reason: StopReason) {
+        self.running = false;
+        self.stop_reason = Some(reason);
+    }
+
+    fn stop_reason(&self) -> StopReason {
+        self.stop_reason.unwrap_or(StopReason::Stopped)
+    }
+}
+
+/// Splits an object file into its origin and the words to load there
+fn parse_object(bytes: &[u8]) -> Result<(MemoryLocationSize, Vec<u16>), LoadError> {
+    if bytes.len() < 2 {
+        return Err(LoadError::MissingOrigin);
+    }
+    if !bytes.len().is_multiple_of(2) {
+        return Err(LoadError::OddByteCount);
+    }
+
+    let origin = u16::from_be_bytes([bytes[0], bytes[1]]);
+    let words = bytes[2..]
+        .chunks_exact(2)
+        .map(|word| u16::from_be_bytes([word[0], word[1]]))
+        .collect();
+
+    Ok((origin, words))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use io::{BufferedIo, Utf8Io};
+    use std::sync::{Arc, Mutex};
+
+    #[test]
+    fn add_register() {
+        let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]);
+        machine.registers[2] = 5;
+        machine.registers[3] = 6;
+        machine.exec_instruction(Instruction::AddRegister(AddRegister {
+            dr: 1,
+            sr1: 2,
+            sr2: 3,
+        }));
+
+        assert_eq!(machine.registers[1], 11);
+        assert_eq!(machine.cond, CondFlag::POSITIVE);
+        assert_eq!(machine.pc, PROGRAM_START + 1);
+    }
+
+    #[test]
+    fn step_returns_instruction() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let add = Instruction::AddRegister(AddRegister {
+            dr: 1,
+            sr1: 2,
+            sr2: 3,
+        });
+        memory[PROGRAM_START as usize] = u16::from_be(add.encode());
+
+        let mut machine = LC3::from_start_state(memory);
+
+        assert_eq!(machine.step(), Ok(add));
+    }
+
+    #[test]
+    fn last_executed() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let jsr = Instruction::JumpSubRoutineOffset(JumpSubRoutineOffset { pc_offset11: 5 });
+        memory[PROGRAM_START as usize] = u16::from_be(jsr.encode());
+
+        let mut machine = LC3::from_start_state(memory);
+        assert_eq!(machine.last_pc(), None);
+        assert_eq!(machine.last_instruction(), None);
+
+        machine.step().unwrap();
+        assert_eq!(machine.last_pc(), Some(PROGRAM_START));
+        assert_eq!(machine.last_instruction(), Some(jsr));
+
+        machine.step_unchecked();
+        assert_eq!(machine.last_pc(), Some(PROGRAM_START + 6));
+        assert_eq!(
+            machine.last_instruction(),
+            Some(Instruction::Branch(Branch {
+                nzp: CondFlag::empty(),
+                pc_offset9: 0
+            }))
+        );
+    }
+
+    #[test]
+    fn step_reserved_opcode() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        memory[PROGRAM_START as usize] = 0xD000;
+
+        let mut machine = LC3::from_start_state(memory);
+
+        assert_eq!(
+            machine.step(),
+            Err(StepError::Decode(DecodeError::ReservedOpCode(0xD000)))
+        );
+        assert_eq!(machine.pc, PROGRAM_START);
+        assert_eq!(machine.cycles, 0);
+    }
+
+    #[test]
+    fn fetch_decode_execute_matches_step() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let program = [
+            Instruction::AddImmediate(AddImmediate {
+                dr: 1,
+                sr1: 1,
+                imm5: 3,
+            }),
+            Instruction::JumpSubRoutineOffset(JumpSubRoutineOffset { pc_offset11: 2 }),
+        ];
+        for (i, instr) in program.iter().enumerate() {
+            memory[PROGRAM_START as usize + i] = u16::from_be(instr.encode());
+        }
+
+        let mut stepped = LC3::from_start_state(memory);
+        let mut manual = LC3::from_start_state(memory);
+        for _ in 0..program.len() {
+            let expected = stepped.step().unwrap();
+
+            let raw_instr = manual.fetch();
+            let instr = manual.decode(raw_instr).unwrap();
+            manual.execute(instr).unwrap();
+
+            assert_eq!(instr, expected);
+            assert_eq!(manual.pc, stepped.pc);
+            assert_eq!(manual.registers, stepped.registers);
+            assert_eq!(manual.cond, stepped.cond);
+            assert_eq!(manual.cycles, stepped.cycles);
+        }
+    }
+
+    #[test]
+    fn add_immediate() {
         let mut memory = [0; MAX_MEMORY_SIZE];
         let dr = 1;
         let sr1 = 2;
@@ -299,7 +1551,7 @@ mod tests {
 
         let mut machine = LC3::from_start_state(memory);
         machine.registers[sr1 as usize] = 5;
-        machine.step();
+        machine.step().unwrap();
 
         assert_eq!(machine.registers[dr as usize], 11);
         assert_eq!(machine.cond, CondFlag::POSITIVE);
@@ -323,7 +1575,7 @@ mod tests {
         let mut machine = LC3::from_start_state(memory);
         machine.registers[sr1 as usize] = negative_one;
         machine.registers[sr2 as usize] = negative_one;
-        machine.step();
+        machine.step().unwrap();
 
         assert_eq!(machine.registers[dr as usize], negative_two);
         assert_eq!(machine.cond, CondFlag::NEGATIVE);
@@ -346,7 +1598,7 @@ mod tests {
         let mut machine = LC3::from_start_state(memory);
         machine.registers[sr1 as usize] = 1;
         machine.registers[sr2 as usize] = negative_one;
-        machine.step();
+        machine.step().unwrap();
 
         assert_eq!(machine.registers[dr as usize], 0);
         assert_eq!(machine.cond, CondFlag::ZERO);
@@ -365,7 +1617,7 @@ mod tests {
 
         let mut machine = LC3::from_start_state(memory);
         machine.registers[sr1 as usize] = 1;
-        machine.step();
+        machine.step().unwrap();
 
         assert_eq!(machine.registers[dr as usize], 0);
         assert_eq!(machine.cond, CondFlag::ZERO);
@@ -385,7 +1637,7 @@ mod tests {
         memory[0xFFFE] = 17;
 
         let mut machine = LC3::from_start_state(memory);
-        machine.step();
+        machine.step().unwrap();
 
         assert_eq!(machine.registers[dr as usize], 17);
         assert_eq!(machine.cond, CondFlag::POSITIVE);
@@ -405,7 +1657,7 @@ mod tests {
         let mut machine = LC3::from_start_state(memory);
         machine.registers[sr1 as usize] = 0b0101;
         machine.registers[sr2 as usize] = 0b1110;
-        machine.step();
+        machine.step().unwrap();
 
         let expected = 0b0100;
         assert_eq!(machine.registers[dr as usize], expected);
@@ -425,7 +1677,7 @@ mod tests {
 
         let mut machine = LC3::from_start_state(memory);
         machine.registers[sr1 as usize] = 0xFFF3;
-        machine.step();
+        machine.step().unwrap();
 
         let expected = 0xFFF1;
         assert_eq!(machine.registers[dr as usize], expected);
@@ -443,7 +1695,7 @@ mod tests {
 
         let mut machine = LC3::from_start_state(memory);
         machine.cond = CondFlag::POSITIVE;
-        machine.step();
+        machine.step().unwrap();
 
         assert_eq!(machine.pc, PROGRAM_START + 11);
     }
@@ -459,7 +1711,7 @@ mod tests {
 
         let mut machine = LC3::from_start_state(memory);
         machine.cond = CondFlag::NEGATIVE;
-        machine.step();
+        machine.step().unwrap();
 
         assert_eq!(machine.pc, PROGRAM_START + 1);
     }
@@ -474,7 +1726,7 @@ mod tests {
 
         let mut machine = LC3::from_start_state(memory);
         machine.registers[base_r as usize] = 0xFFFF;
-        machine.step();
+        machine.step().unwrap();
 
         assert_eq!(machine.pc, 0xFFFF);
     }
@@ -490,7 +1742,7 @@ mod tests {
         memory[PROGRAM_START as usize] = instruction;
 
         let mut machine = LC3::from_start_state(memory);
-        machine.step();
+        machine.step().unwrap();
 
         assert_eq!(machine.pc, PROGRAM_START + 11);
         assert_eq!(machine.registers[7], PROGRAM_START + 1);
@@ -509,7 +1761,7 @@ mod tests {
         let jump_to = 0xFFFF;
         let mut machine = LC3::from_start_state(memory);
         machine.registers[base_r as usize] = jump_to;
-        machine.step();
+        machine.step().unwrap();
 
         assert_eq!(machine.pc, 0xFFFF);
         assert_eq!(machine.registers[7], PROGRAM_START + 1);
@@ -526,7 +1778,7 @@ mod tests {
         memory[PROGRAM_START as usize + 1 + 10] = 17;
 
         let mut machine = LC3::from_start_state(memory);
-        machine.step();
+        machine.step().unwrap();
 
         assert_eq!(machine.registers[dr as usize], 17);
         assert_eq!(machine.cond, CondFlag::POSITIVE);
@@ -552,11 +1804,167 @@ mod tests {
 
         let mut machine = LC3::from_start_state(memory);
         machine.registers[base_r as usize] = 7;
-        machine.step();
+        machine.step().unwrap();
 
         assert_eq!(machine.registers[dr as usize], 17);
     }
 
+    #[test]
+    fn load_base_offset_wraps() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let dr = 1;
+        let base_r = 2;
+        let pc_offset6 = 3;
+
+        let instruction = u16::from_be(
+            Instruction::LoadBaseOffset(LoadBaseOffset {
+                dr,
+                base_r,
+                pc_offset6,
+            })
+            .encode(),
+        );
+        memory[PROGRAM_START as usize] = instruction;
+        memory[2] = 17;
+
+        let mut machine = LC3::from_start_state(memory);
+        machine.registers[base_r as usize] = 0xFFFF;
+        machine.step().unwrap();
+
+        assert_eq!(machine.registers[dr as usize], 17);
+    }
+
+    #[test]
+    fn negative_base_offset() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let load = Instruction::LoadBaseOffset(LoadBaseOffset {
+            dr: 1,
+            base_r: 2,
+            pc_offset6: 0xFFFF, // -1
+        });
+        let store = Instruction::StoreBaseOffset(StoreBaseOffset {
+            sr: 1,
+            base_r: 2,
+            pc_offset6: 0xFFFE, // -2
+        });
+        memory[PROGRAM_START as usize] = u16::from_be(load.encode());
+        memory[PROGRAM_START as usize + 1] = u16::from_be(store.encode());
+        memory[0x3FFF] = 17;
+
+        let mut machine = LC3::from_start_state(memory);
+        machine.registers[2] = 0x4000;
+        assert_eq!(machine.step(), Ok(load));
+        assert_eq!(machine.registers[1], 17);
+        assert_eq!(machine.step(), Ok(store));
+        assert_eq!(machine.memory[0x3FFE], 17);
+    }
+
+    /// The values loads are tested with and the cond flag each one should set
+    const LOADED_CONDS: [(u16, CondFlag); 3] = [
+        (0x8000, CondFlag::NEGATIVE),
+        (0, CondFlag::ZERO),
+        (0x7FFF, CondFlag::POSITIVE),
+    ];
+
+    #[test]
+    fn load_sets_cond() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let instruction = Instruction::Load(Load {
+            dr: 1,
+            pc_offset9: 0,
+        });
+        memory[PROGRAM_START as usize] = u16::from_be(instruction.encode());
+        let mut machine = LC3::from_start_state(memory);
+
+        for &(value, cond) in &LOADED_CONDS {
+            machine.memory[PROGRAM_START as usize + 1] = value;
+            machine.pc = PROGRAM_START;
+            machine.step().unwrap();
+            assert_eq!(machine.cond, cond);
+
+            // LD also goes through the fast path
+            machine.pc = PROGRAM_START;
+            machine.cond = CondFlag::empty();
+            machine.step_unchecked();
+            assert_eq!(machine.cond, cond);
+        }
+    }
+
+    #[test]
+    fn load_base_offset_sets_cond() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let instruction = Instruction::LoadBaseOffset(LoadBaseOffset {
+            dr: 1,
+            base_r: 2,
+            pc_offset6: 0,
+        });
+        memory[PROGRAM_START as usize] = u16::from_be(instruction.encode());
+        let mut machine = LC3::from_start_state(memory);
+        machine.registers[2] = 0x4000;
+
+        for &(value, cond) in &LOADED_CONDS {
+            machine.memory[0x4000] = value;
+            machine.pc = PROGRAM_START;
+            machine.step().unwrap();
+            assert_eq!(machine.cond, cond);
+        }
+    }
+
+    #[test]
+    fn load_indirect_sets_cond() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let instruction = Instruction::LoadIndirect(LoadIndirect {
+            dr: 1,
+            pc_offset9: 0,
+        });
+        memory[PROGRAM_START as usize] = u16::from_be(instruction.encode());
+        memory[PROGRAM_START as usize + 1] = 0x4000;
+        let mut machine = LC3::from_start_state(memory);
+
+        for &(value, cond) in &LOADED_CONDS {
+            machine.memory[0x4000] = value;
+            machine.pc = PROGRAM_START;
+            machine.step().unwrap();
+            assert_eq!(machine.cond, cond);
+        }
+    }
+
+    #[test]
+    fn jump_table() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let table_base: u16 = 0x4000;
+        let targets = [0x5000, 0x6000, 0x7000];
+        for (i, &target) in targets.iter().enumerate() {
+            memory[table_base as usize + i] = target;
+        }
+
+        let program = [
+            Instruction::AddRegister(AddRegister {
+                dr: 1,
+                sr1: 1,
+                sr2: 0,
+            }),
+            Instruction::LoadBaseOffset(LoadBaseOffset {
+                dr: 2,
+                base_r: 1,
+                pc_offset6: 0,
+            }),
+            Instruction::Jump(Jump { base_r: 2 }),
+        ];
+        for (i, instr) in program.iter().enumerate() {
+            memory[PROGRAM_START as usize + i] = u16::from_be(instr.encode());
+        }
+
+        let mut machine = LC3::from_start_state(memory);
+        machine.registers[0] = 2;
+        machine.registers[1] = table_base;
+        let expected = machine.compute_jump_target(table_base, 2);
+        machine.step_n(program.len());
+
+        assert_eq!(expected, 0x7000);
+        assert_eq!(machine.pc, expected);
+    }
+
     #[test]
     fn load_effective_address() {
         let mut memory = [0; MAX_MEMORY_SIZE];
@@ -569,7 +1977,7 @@ mod tests {
         memory[PROGRAM_START as usize] = instruction;
 
         let mut machine = LC3::from_start_state(memory);
-        machine.step();
+        machine.step().unwrap();
 
         assert_eq!(machine.registers[dr as usize], PROGRAM_START + 11);
     }
@@ -585,7 +1993,7 @@ mod tests {
 
         let mut machine = LC3::from_start_state(memory);
         machine.registers[sr1 as usize] = 0xF0F0;
-        machine.step();
+        machine.step().unwrap();
 
         assert_eq!(machine.registers[dr as usize], 0x0F0F);
     }
@@ -601,7 +2009,7 @@ mod tests {
 
         let mut machine = LC3::from_start_state(memory);
         machine.registers[sr as usize] = 17;
-        machine.step();
+        machine.step().unwrap();
 
         let updated_address = (PROGRAM_START + pc_offset9 + 1) as usize;
         assert_eq!(machine.memory[updated_address], 17);
@@ -623,7 +2031,7 @@ mod tests {
 
         let mut machine = LC3::from_start_state(memory);
         machine.registers[sr as usize] = 17;
-        machine.step();
+        machine.step().unwrap();
 
         assert_eq!(machine.memory[direct_address as usize], 17);
     }
@@ -653,14 +2061,424 @@ mod tests {
         let mut machine = LC3::from_start_state(memory);
         machine.registers[base_r as usize] = base_r_value;
         machine.registers[sr as usize] = sr_value;
-        machine.step();
+        machine.step().unwrap();
 
-        let updated_address = base_r_value + pc_offset6 as u16;
+        let updated_address = base_r_value + pc_offset6;
         assert_eq!(machine.memory[updated_address as usize], sr_value);
     }
 
     #[test]
-    #[ignore] // unignore to see puts output
+    fn fast_dispatch_matches_decoding() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let program = [
+            Instruction::Load(Load {
+                dr: 0,
+                pc_offset9: 6,
+            }),
+            Instruction::AddImmediate(AddImmediate {
+                dr: 1,
+                sr1: 1,
+                imm5: 3,
+            }),
+            Instruction::AndImmediate(AndImmediate {
+                dr: 2,
+                sr1: 1,
+                imm5: 0x1E, // negative two
+            }),
+            Instruction::AddRegister(AddRegister {
+                dr: 3,
+                sr1: 3,
+                sr2: 2,
+            }),
+            Instruction::AddImmediate(AddImmediate {
+                dr: 0,
+                sr1: 0,
+                imm5: 0x1F, // negative one
+            }),
+            Instruction::Branch(Branch {
+                nzp: CondFlag::POSITIVE,
+                pc_offset9: 0xFFFB, // negative five
+            }),
+            Instruction::Trap(Trap {
+                vect8: TrapCode::Halt,
+            }),
+        ];
+        for (i, instr) in program.iter().enumerate() {
+            memory[PROGRAM_START as usize + i] = u16::from_be(instr.encode());
+        }
+        memory[PROGRAM_START as usize + 7] = 10;
+
+        let mut fast = LC3::from_start_state(memory).with_io(BufferedIo::default());
+        fast.run();
+        let mut decoded = LC3::from_start_state(memory).with_io(BufferedIo::default());
+        decoded.fast_dispatch = false;
+        decoded.run();
+
+        assert_eq!(fast.registers, decoded.registers);
+        assert_eq!(fast.registers[3], 160);
+        assert_eq!(fast.cond, decoded.cond);
+        assert_eq!(fast.pc, decoded.pc);
+        assert_eq!(fast.cycles, decoded.cycles);
+    }
+
+    #[test]
+    fn from_bytes() {
+        let bytes = [0x30, 0x00, 0x12, 0x34, 0x56, 0x78];
+
+        let machine = LC3::from_bytes(&bytes).unwrap();
+
+        assert_eq!(machine.pc, PROGRAM_START);
+        assert_eq!(machine.memory[PROGRAM_START as usize], 0x1234);
+        assert_eq!(machine.memory[PROGRAM_START as usize + 1], 0x5678);
+    }
+
+    #[test]
+    fn to_obj_round_trip() {
+        let bytes = [0x30, 0x00, 0x12, 0x34, 0x56, 0x78];
+        let machine = LC3::from_bytes(&bytes).unwrap();
+
+        let obj = machine.to_obj(PROGRAM_START..PROGRAM_START + 2);
+        assert_eq!(obj, bytes);
+
+        let reloaded = LC3::from_bytes(&obj).unwrap();
+        assert_eq!(reloaded.pc, PROGRAM_START);
+        assert_eq!(reloaded.memory_image(), machine.memory_image());
+    }
+
+    #[test]
+    fn from_bytes_missing_origin() {
+        assert_eq!(
+            LC3::from_bytes(&[0x30]).err(),
+            Some(LoadError::MissingOrigin)
+        );
+    }
+
+    #[test]
+    fn from_bytes_odd_byte_count() {
+        let bytes = [0x30, 0x00, 0x12, 0x34, 0x56];
+
+        assert_eq!(LC3::from_bytes(&bytes).err(), Some(LoadError::OddByteCount));
+    }
+
+    #[test]
+    fn load_additional_keeps_first_origin() {
+        // LD R1, #2; JSRR R1; HALT; .FILL x0200
+        let user = [0x30, 0x00, 0x22, 0x02, 0x40, 0x40, 0xF0, 0x25, 0x02, 0x00];
+        // ADD R0, R0, #5; RET
+        let os = [0x02, 0x00, 0x10, 0x25, 0xC1, 0xC0];
+        let mut machine = LC3::from_bytes(&user)
+            .unwrap()
+            .with_io(BufferedIo::default());
+
+        machine.load_additional(&os).unwrap();
+
+        assert_eq!(machine.pc, PROGRAM_START);
+        assert_eq!(machine.run(), StopReason::Halted { code: 0 });
+        assert_eq!(machine.registers[0], 5);
+        assert_eq!(machine.memory[PROGRAM_START as usize], 0x2202);
+    }
+
+    #[test]
+    fn protect_system_memory_rejects_os_region() {
+        let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]);
+        machine.protect_system_memory = true;
+
+        assert_eq!(
+            machine.load_additional(&[0x00, 0x00, 0x12, 0x34]),
+            Err(LoadError::ReservedRegion { origin: 0, len: 1 })
+        );
+        assert_eq!(
+            machine.load_words_at(0xFDFF, &[1, 2]),
+            Err(LoadError::ReservedRegion {
+                origin: 0xFDFF,
+                len: 2
+            })
+        );
+        assert_eq!(machine.memory[0], 0);
+
+        machine.load_words_at(0xFDFE, &[1, 2]).unwrap();
+        assert_eq!(machine.memory[0xFDFF], 2);
+    }
+
+    #[test]
+    fn load_words_at_end_of_memory() {
+        let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]);
+
+        machine.load_words_at(0xFFFE, &[1, 2]).unwrap();
+
+        assert_eq!(machine.memory[0xFFFE], 1);
+        assert_eq!(machine.memory[0xFFFF], 2);
+    }
+
+    #[test]
+    fn load_words_at_too_large() {
+        let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]);
+
+        let result = machine.load_words_at(0xFFFE, &[1, 2, 3, 4]);
+
+        assert_eq!(
+            result,
+            Err(LoadError::TooLarge {
+                origin: 0xFFFE,
+                len: 4
+            })
+        );
+        assert_eq!(machine.memory[0xFFFE], 0);
+    }
+
+    #[test]
+    fn memory_image_round_trip() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        memory[0] = 1;
+        memory[PROGRAM_START as usize] = 2;
+        memory[MAX_MEMORY_SIZE - 1] = 3;
+        let machine = LC3::from_start_state(memory);
+
+        let image = machine.memory_image();
+        let mut other = LC3::from_start_state([0xFFFF; MAX_MEMORY_SIZE]);
+        other.load_image(&image);
+
+        assert_eq!(image.len(), MAX_MEMORY_SIZE);
+        assert_eq!(&other.memory[..], &machine.memory[..]);
+    }
+
+    #[test]
+    fn load_partial_image() {
+        let mut machine = LC3::from_start_state([0xFFFF; MAX_MEMORY_SIZE]);
+
+        machine.load_image(&[1, 2]);
+
+        assert_eq!(&machine.memory[..3], &[1, 2, 0xFFFF]);
+    }
+
+    #[test]
+    fn run_generator_yields_until_halt() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+
+        // Empty memory decodes as a branch that never jumps so everything up to the HALT is a nop
+        let halt_offset = GENERATOR_SLICE * 2 + GENERATOR_SLICE / 2;
+        let vect8 = TrapCode::Halt;
+        let instruction = u16::from_be(Instruction::Trap(Trap { vect8 }).encode());
+        memory[PROGRAM_START as usize + halt_offset] = instruction;
+
+        let mut machine = LC3::from_start_state(memory);
+        {
+            let mut generator = machine.run_generator();
+            assert_eq!(generator(), None);
+            assert_eq!(generator(), None);
+            assert_eq!(generator(), Some(StopReason::Halted { code: 0 }));
+            assert_eq!(generator(), Some(StopReason::Halted { code: 0 }));
+        }
+
+        assert_eq!(machine.pc, PROGRAM_START + halt_offset as u16 + 1);
+    }
+
+    #[test]
+    fn gets() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+
+        let vect8 = TrapCode::Gets;
+        let buffer: u16 = 0x4000;
+
+        let instruction = u16::from_be(Instruction::Trap(Trap { vect8 }).encode());
+        memory[PROGRAM_START as usize] = instruction;
+        memory[buffer as usize + 5] = 0xFFFF;
+
+        let mut machine = LC3::from_start_state(memory).with_io(BufferedIo::new(b"hello\nworld"));
+        machine.extended_traps = true;
+        machine.registers[0] = buffer;
+        machine.step().unwrap();
+
+        let line: Vec<u16> = b"hello\0".iter().map(|&ch| ch as u16).collect();
+        assert_eq!(
+            &machine.memory[buffer as usize..buffer as usize + 6],
+            &line[..]
+        );
+        assert_eq!(machine.registers[1], 5);
+        assert_eq!(machine.io.input, b"world");
+    }
+
+    #[test]
+    fn store_base_offset_wraps() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let base_r = 1;
+        let sr = 2;
+        let pc_offset6 = 3;
+
+        let instruction = u16::from_be(
+            Instruction::StoreBaseOffset(StoreBaseOffset {
+                sr,
+                pc_offset6,
+                base_r,
+            })
+            .encode(),
+        );
+        memory[PROGRAM_START as usize] = instruction;
+
+        let mut machine = LC3::from_start_state(memory);
+        machine.registers[base_r as usize] = 0xFFFF;
+        machine.registers[sr as usize] = 17;
+        machine.step().unwrap();
+
+        assert_eq!(machine.memory[2], 17);
+    }
+
+    #[test]
+    fn nop_never_branches() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        memory[PROGRAM_START as usize] =
+            u16::from_be(Instruction::Branch(Branch::nop(10)).encode());
+        let mut machine = LC3::from_start_state(memory);
+
+        for &cond in &[CondFlag::NEGATIVE, CondFlag::ZERO, CondFlag::POSITIVE] {
+            machine.pc = PROGRAM_START;
+            machine.cond = cond;
+            machine.step().unwrap();
+
+            assert_eq!(machine.pc, PROGRAM_START + 1);
+            assert_eq!(machine.cond, cond);
+        }
+    }
+
+    #[test]
+    fn branch_backward() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let nzp = CondFlag::POSITIVE;
+        let pc_offset9 = 0xFFFE; // negative two
+
+        let instruction = u16::from_be(Instruction::Branch(Branch { nzp, pc_offset9 }).encode());
+        memory[PROGRAM_START as usize] = instruction;
+
+        let mut machine = LC3::from_start_state(memory);
+        machine.cond = CondFlag::POSITIVE;
+        machine.step().unwrap();
+
+        assert_eq!(machine.pc, PROGRAM_START - 1);
+    }
+
+    #[test]
+    fn gets_requires_extended_traps() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+
+        let vect8 = TrapCode::Gets;
+        let instruction = u16::from_be(Instruction::Trap(Trap { vect8 }).encode());
+        memory[PROGRAM_START as usize] = instruction;
+
+        let mut machine = LC3::from_start_state(memory).with_io(BufferedIo::new(b"hello\n"));
+
+        assert_eq!(
+            machine.step(),
+            Err(StepError::ExtendedTrapDisabled(TrapCode::Gets))
+        );
+        assert_eq!(machine.pc, PROGRAM_START);
+        assert_eq!(machine.io.input, b"hello\n");
+    }
+
+    #[test]
+    fn extended_trap_handler_runs_without_extended_traps() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+
+        let vect8 = TrapCode::Gets;
+        let instruction = u16::from_be(Instruction::Trap(Trap { vect8 }).encode());
+        memory[PROGRAM_START as usize] = instruction;
+
+        let mut machine = LC3::from_start_state(memory).with_io(BufferedIo::default());
+        machine.set_trap_handler(TrapCode::Gets, Box::new(|machine| machine.registers[0] = 7));
+
+        assert_eq!(machine.step(), Ok(Instruction::Trap(Trap { vect8 })));
+        assert_eq!(machine.registers[0], 7);
+    }
+
+    #[test]
+    #[should_panic]
+    fn step_unchecked_panics() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        memory[PROGRAM_START as usize] = 0xD000;
+
+        let mut machine = LC3::from_start_state(memory);
+        machine.step_unchecked();
+    }
+
+    #[test]
+    fn putn() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+
+        let vect8 = TrapCode::PutN;
+        let instruction = u16::from_be(Instruction::Trap(Trap { vect8 }).encode());
+        memory[PROGRAM_START as usize] = instruction;
+        memory[PROGRAM_START as usize + 1] = instruction;
+
+        let mut machine = LC3::from_start_state(memory).with_io(BufferedIo::default());
+        machine.extended_traps = true;
+        machine.registers[0] = 0xFFFF;
+        machine.step().unwrap();
+        assert_eq!(machine.io.output, b"-1");
+
+        machine.io.output.clear();
+        machine.registers[0] = 1234;
+        machine.step().unwrap();
+        assert_eq!(machine.io.output, b"1234");
+    }
+
+    #[test]
+    fn halt_exit_code() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+
+        let vect8 = TrapCode::Halt;
+        let instruction = u16::from_be(Instruction::Trap(Trap { vect8 }).encode());
+        memory[PROGRAM_START as usize] = instruction;
+
+        let mut machine = LC3::from_start_state(memory).with_io(BufferedIo::default());
+        machine.registers[0] = 5;
+        assert_eq!(machine.run(), StopReason::Halted { code: 0 });
+
+        let mut machine = LC3::from_start_state(memory).with_io(BufferedIo::default());
+        machine.halt_exit_code = true;
+        machine.registers[0] = 5;
+        assert_eq!(machine.run(), StopReason::Halted { code: 5 });
+    }
+
+    #[test]
+    fn trap_handler() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+
+        let vect8 = TrapCode::Out;
+        let instruction = u16::from_be(Instruction::Trap(Trap { vect8 }).encode());
+        memory[PROGRAM_START as usize] = instruction;
+        memory[PROGRAM_START as usize + 1] = instruction;
+
+        let mut machine = LC3::from_start_state(memory).with_io(BufferedIo::default());
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let handler_written = Arc::clone(&written);
+        machine.set_trap_handler(
+            TrapCode::Out,
+            Box::new(move |machine| handler_written.lock().unwrap().push(machine.registers[0])),
+        );
+        machine.registers[0] = b'a' as u16;
+        machine.step().unwrap();
+        machine.registers[0] = b'b' as u16;
+        machine.step().unwrap();
+
+        assert_eq!(*written.lock().unwrap(), vec![b'a' as u16, b'b' as u16]);
+        assert_eq!(machine.io.output, b"");
+    }
+
+    #[test]
+    fn clear_screen() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+
+        let vect8 = TrapCode::ClrScr;
+        let instruction = u16::from_be(Instruction::Trap(Trap { vect8 }).encode());
+        memory[PROGRAM_START as usize] = instruction;
+
+        let mut machine = LC3::from_start_state(memory).with_io(BufferedIo::default());
+        machine.extended_traps = true;
+        machine.step().unwrap();
+
+        assert_eq!(machine.io.output, b"\x1b[2J\x1b[H");
+    }
+
+    #[test]
     fn puts() {
         let mut memory = [0; MAX_MEMORY_SIZE];
 
@@ -674,10 +2492,390 @@ mod tests {
             memory[i + string_start as usize] = *ch as u16;
         }
 
-        let mut machine = LC3::from_start_state(memory);
+        let mut machine = LC3::from_start_state(memory).with_io(BufferedIo::default());
         machine.registers[0] = string_start;
-        machine.step();
+        machine.step().unwrap();
+
+        assert_eq!(machine.io.output, string);
+    }
+
+    #[test]
+    fn out_multi_byte_utf8() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let out = u16::from_be(
+            Instruction::Trap(Trap {
+                vect8: TrapCode::Out,
+            })
+            .encode(),
+        );
+        memory[PROGRAM_START as usize] = out;
+        memory[PROGRAM_START as usize + 1] = out;
+
+        let mut machine = LC3::from_start_state(memory).with_io(Utf8Io::default());
+        // 'é' is 0xC3 0xA9 in UTF-8
+        machine.registers[0] = 0xC3;
+        machine.step().unwrap();
+        assert_eq!(machine.io.output, "");
+
+        machine.registers[0] = 0xA9;
+        machine.step().unwrap();
+        assert_eq!(machine.io.output, "é");
+    }
+
+    fn add_overflow(saturating_add: bool, a: RegisterSize, b: RegisterSize) -> RegisterSize {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let add = Instruction::AddRegister(AddRegister {
+            dr: 0,
+            sr1: 1,
+            sr2: 2,
+        });
+        memory[PROGRAM_START as usize] = u16::from_be(add.encode());
+
+        let mut machine = LC3::from_start_state(memory);
+        machine.saturating_add = saturating_add;
+        machine.registers[1] = a;
+        machine.registers[2] = b;
+        machine.step().unwrap();
+        machine.registers[0]
+    }
+
+    #[test]
+    fn saturating_add() {
+        assert_eq!(add_overflow(true, 0x7FFF, 1), 0x7FFF);
+        assert_eq!(add_overflow(true, 0x8000, 0xFFFF), 0x8000);
+        assert_eq!(add_overflow(true, 0x7FFE, 1), 0x7FFF);
+    }
+
+    #[test]
+    fn wrapping_add() {
+        assert_eq!(add_overflow(false, 0x7FFF, 1), 0x8000);
+        assert_eq!(add_overflow(false, 0x8000, 0xFFFF), 0x7FFF);
+    }
+
+    fn putsp(packed: &[u16]) -> Vec<u8> {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let putsp = Instruction::Trap(Trap {
+            vect8: TrapCode::PutsP,
+        });
+        memory[PROGRAM_START as usize] = u16::from_be(putsp.encode());
+        let string_start = 0x4000;
+        memory[string_start..string_start + packed.len()].copy_from_slice(packed);
+
+        let mut machine = LC3::from_start_state(memory).with_io(BufferedIo::default());
+        machine.registers[0] = string_start as u16;
+        machine.step().unwrap();
+        machine.io.output
+    }
+
+    #[test]
+    fn putsp_even_length() {
+        assert_eq!(putsp(&[0x6261, 0x6463, 0x0000]), b"abcd");
+    }
+
+    #[test]
+    fn putsp_odd_length() {
+        assert_eq!(putsp(&[0x6261, 0x0063, 0x7878]), b"abc");
+    }
+
+    #[test]
+    fn run_from_subroutine() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let encode = |instr: Instruction| u16::from_be(instr.encode());
+        let halt = 0x2FFF;
+        let subroutine = 0x4000;
+        memory[halt] = encode(Instruction::Trap(Trap {
+            vect8: TrapCode::Halt,
+        }));
+        // Doubles R0
+        memory[subroutine] = encode(Instruction::AddRegister(AddRegister {
+            dr: 0,
+            sr1: 0,
+            sr2: 0,
+        }));
+        memory[subroutine + 1] = encode(Instruction::Jump(Jump { base_r: 7 }));
+
+        let mut machine = LC3::from_start_state(memory).with_io(BufferedIo::default());
+        machine.registers[0] = 21;
+        machine.registers[7] = halt as u16;
+
+        assert_eq!(
+            machine.run_from(subroutine as u16),
+            StopReason::Halted { code: 0 }
+        );
+        assert_eq!(machine.registers[0], 42);
+        assert_eq!(machine.cycles, 3);
+    }
+
+    #[test]
+    fn and_immediate_negative_one() {
+        let and = Instruction::AndImmediate(AndImmediate {
+            dr: 0,
+            sr1: 1,
+            imm5: 0xFFFF, // -1, sign-extended the way decoding leaves it
+        });
+        assert_eq!(Instruction::decode(u16::from_be(and.encode())), and);
+
+        let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]);
+        machine.registers[1] = 0xA5C3;
+        machine.exec_instruction(and);
+
+        assert_eq!(machine.registers[0], 0xA5C3);
+        assert_eq!(machine.registers[1], 0xA5C3);
+        assert_eq!(machine.cond, CondFlag::NEGATIVE);
+    }
+
+    #[test]
+    fn run_fast_matches_run() {
+        assert_run_fast_matches_run(DecodeMode::Lenient);
+        assert_run_fast_matches_run(DecodeMode::Strict);
+    }
+
+    #[test]
+    #[should_panic(expected = "malformed")]
+    fn run_fast_strict_rejects_malformed() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        // ADD R1, R2, R3 with bits 3 and 4 set
+        memory[PROGRAM_START as usize] = 0x128B;
+        let mut machine = LC3::from_start_state(memory).with_io(BufferedIo::default());
+        machine.decode_mode = DecodeMode::Strict;
+
+        machine.run_fast();
+    }
+
+    fn assert_run_fast_matches_run(decode_mode: DecodeMode) {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let program = [
+            Instruction::AddImmediate(AddImmediate {
+                dr: 0,
+                sr1: 0,
+                imm5: 10,
+            }),
+            Instruction::AddRegister(AddRegister {
+                dr: 1,
+                sr1: 1,
+                sr2: 0,
+            }),
+            Instruction::StoreIndirect(StoreIndirect {
+                sr: 1,
+                pc_offset9: 4,
+            }),
+            Instruction::AddImmediate(AddImmediate {
+                dr: 0,
+                sr1: 0,
+                imm5: 0x1F, // negative one
+            }),
+            Instruction::Branch(Branch {
+                nzp: CondFlag::POSITIVE,
+                pc_offset9: 0xFFFC, // back to the first ADD R1
+            }),
+            Instruction::Trap(Trap {
+                vect8: TrapCode::Halt,
+            }),
+        ];
+        for (i, instr) in program.iter().enumerate() {
+            memory[PROGRAM_START as usize + i] = u16::from_be(instr.encode());
+        }
+        memory[PROGRAM_START as usize + 7] = 0x4000;
+
+        let mut slow = LC3::from_start_state(memory).with_io(BufferedIo::default());
+        let mut fast = LC3::from_start_state(memory).with_io(BufferedIo::default());
+        slow.decode_mode = decode_mode;
+        fast.decode_mode = decode_mode;
+
+        assert_eq!(fast.run_fast(), slow.run());
+        assert_eq!(fast.state(), slow.state());
+        assert_eq!(fast.cycles, slow.cycles);
+        assert_eq!(fast.io.output, slow.io.output);
+        assert_eq!(fast.memory[0x4000], 55);
+    }
+
+    #[test]
+    fn dump_memory_annotated() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        memory[0x3000] = b'A' as u16;
+        memory[0x3001] = 0xFFFF;
+        memory[0x3002] = 0x8000;
+
+        let machine = LC3::from_start_state(memory);
+
+        assert_eq!(
+            machine.dump_memory_annotated(0x3000..0x3004),
+            "x3000  x0041      65  A\n\
+             x3001  xFFFF      -1\n\
+             x3002  x8000  -32768\n\
+             x3003  x0000       0\n"
+        );
+    }
+
+    fn countdown() -> LC3<BufferedIo> {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let program = [
+            Instruction::AddImmediate(AddImmediate {
+                dr: 0,
+                sr1: 0,
+                imm5: 0x1F, // negative one
+            }),
+            Instruction::Branch(Branch {
+                nzp: CondFlag::all(),
+                pc_offset9: 0xFFFE,
+            }),
+        ];
+        for (i, instr) in program.iter().enumerate() {
+            memory[PROGRAM_START as usize + i] = u16::from_be(instr.encode());
+        }
+
+        let mut machine = LC3::from_start_state(memory).with_io(BufferedIo::default());
+        machine.registers[0] = 5;
+        machine
+    }
+
+    #[test]
+    fn run_until_register() {
+        let mut machine = countdown();
+
+        assert_eq!(
+            machine.run_until_register(0, 0, 100),
+            StopReason::RegisterMatched
+        );
+        assert_eq!(machine.registers[0], 0);
+        assert_eq!(machine.cycles, 9);
+        assert_eq!(machine.pc, PROGRAM_START + 1);
+    }
+
+    #[test]
+    fn run_until_register_cycle_limit() {
+        let mut machine = countdown();
+
+        assert_eq!(machine.run_until_register(0, 0, 4), StopReason::CycleLimit);
+        assert_eq!(machine.cycles, 4);
+    }
+
+    #[test]
+    fn with_registers_mut() {
+        let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]);
+        machine.with_registers_mut(|registers| {
+            for (i, register) in registers.iter_mut().enumerate() {
+                *register = 0x100 + i as RegisterSize;
+            }
+        });
+
+        assert_eq!(
+            machine.registers,
+            [0x100, 0x101, 0x102, 0x103, 0x104, 0x105, 0x106, 0x107]
+        );
+        assert_eq!(machine.registers_set, 0xFF);
+    }
+
+    /// OUT then HALT
+    fn out_then_halt() -> LC3<BufferedIo> {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let trap = |vect8| u16::from_be(Instruction::Trap(Trap { vect8 }).encode());
+        memory[PROGRAM_START as usize] = trap(TrapCode::Out);
+        memory[PROGRAM_START as usize + 1] = trap(TrapCode::Halt);
+        LC3::from_start_state(memory).with_io(BufferedIo::default())
+    }
+
+    #[test]
+    fn halt_trap_is_halted() {
+        assert_eq!(out_then_halt().run(), StopReason::Halted { code: 0 });
+    }
+
+    #[test]
+    fn request_stop_is_stopped() {
+        let mut machine = out_then_halt();
+        machine.set_trap_handler(TrapCode::Out, Box::new(|machine| machine.request_stop()));
+
+        assert_eq!(machine.run(), StopReason::Stopped);
+        assert_eq!(machine.pc, PROGRAM_START + 1);
+    }
+
+    #[test]
+    fn boundary_hook_redirects_pc() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let add = |dr| {
+            u16::from_be(
+                Instruction::AddImmediate(AddImmediate {
+                    dr,
+                    sr1: dr,
+                    imm5: 1,
+                })
+                .encode(),
+            )
+        };
+        let halt = u16::from_be(
+            Instruction::Trap(Trap {
+                vect8: TrapCode::Halt,
+            })
+            .encode(),
+        );
+        memory[PROGRAM_START as usize..PROGRAM_START as usize + 4].copy_from_slice(&[
+            add(0),
+            add(0),
+            add(0),
+            halt,
+        ]);
+        memory[0x4000..0x4002].copy_from_slice(&[add(1), halt]);
+        let mut machine = LC3::from_start_state(memory).with_io(BufferedIo::default());
+
+        // Vectors to a handler at x4000 on the third boundary the way an interrupt would
+        let mut boundaries = 0;
+        machine.on_instruction_boundary(Box::new(move |machine| {
+            boundaries += 1;
+            if boundaries == 3 {
+                machine.registers[7] = machine.pc;
+                machine.pc = 0x4000;
+            }
+        }));
+
+        assert_eq!(machine.run(), StopReason::Halted { code: 0 });
+        assert_eq!(machine.registers[0], 2);
+        assert_eq!(machine.registers[1], 1);
+        assert_eq!(machine.registers[7], PROGRAM_START + 2);
+    }
+
+    #[test]
+    fn clearing_running_is_stopped() {
+        let mut machine = out_then_halt();
+        machine.set_trap_handler(TrapCode::Out, Box::new(|machine| machine.running = false));
+
+        assert_eq!(machine.run(), StopReason::Stopped);
+    }
+
+    #[test]
+    fn clearing_mcr_halts() {
+        let mut memory = [0; MAX_MEMORY_SIZE];
+        let program = [
+            // Leaves the clock enable bit set
+            Instruction::StoreIndirect(StoreIndirect {
+                sr: 1,
+                pc_offset9: 4,
+            }),
+            Instruction::AndImmediate(AndImmediate {
+                dr: 0,
+                sr1: 0,
+                imm5: 0,
+            }),
+            Instruction::StoreIndirect(StoreIndirect {
+                sr: 0,
+                pc_offset9: 2,
+            }),
+            Instruction::AddImmediate(AddImmediate {
+                dr: 2,
+                sr1: 2,
+                imm5: 1,
+            }),
+        ];
+        for (i, instr) in program.iter().enumerate() {
+            memory[PROGRAM_START as usize + i] = u16::from_be(instr.encode());
+        }
+        memory[PROGRAM_START as usize + 5] = MCR_ADDRESS;
+
+        let mut machine = LC3::from_start_state(memory).with_io(BufferedIo::default());
+        machine.registers[1] = 0x8000;
 
-        assert!(false);
+        assert_eq!(machine.run(), StopReason::Halted { code: 0 });
+        assert_eq!(machine.memory[MCR_ADDRESS as usize], 0);
+        assert_eq!(machine.registers[2], 0);
+        assert_eq!(machine.cycles, 3);
     }
 }
@@ -1,15 +1,146 @@
-use std::{env, fs::File, io::Read};
+use std::{
+    env,
+    error::Error,
+    fs,
+    io::{self, Cursor},
+    process,
+};
 
-use lilc3::LC3;
+use lilc3::{
+    io::{Io, ReadWriteIo},
+    CondFlag, RegisterSize, StopReason, GENERATOR_SLICE, LC3,
+};
+
+#[cfg(feature = "raw_terminal")]
+use lilc3::terminal::{RawModeGuard, Stty};
+#[cfg(feature = "raw_terminal")]
+use std::io::IsTerminal;
+
+const USAGE: &str = "usage: lilc3 [--input FILE] [--exit-code] [--dump-on-halt] PROGRAM";
 
 fn main() {
-    let file = env::args().nth(1).expect("Filename required");
-    let file = match File::open(&file) {
-        Ok(file) => file,
-        Err(e) => panic!("Failed to open file: {}\n{}", &file, e),
+    if let Err(e) = run() {
+        eprintln!("lilc3: {}", e);
+        process::exit(1);
+    }
+}
+
+fn run() -> Result<(), Box<dyn Error>> {
+    let mut args = env::args().skip(1);
+    let mut input = None;
+    let mut file = None;
+    let mut exit_code = false;
+    let mut dump_on_halt = false;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--input" => {
+                input = Some(
+                    args.next()
+                        .ok_or_else(|| format!("--input requires a file\n{}", USAGE))?,
+                )
+            }
+            "--exit-code" => exit_code = true,
+            "--dump-on-halt" => dump_on_halt = true,
+            flag if flag.starts_with("--") => {
+                return Err(format!("unknown option {}\n{}", flag, USAGE).into())
+            }
+            _ => file = Some(arg),
+        }
+    }
+
+    let file = file.ok_or_else(|| format!("no program given\n{}", USAGE))?;
+    let bytes = fs::read(&file).map_err(|e| format!("failed to read {}: {}", file, e))?;
+    let mut machine =
+        LC3::from_bytes(&bytes).map_err(|e| format!("failed to load {}: {}", file, e))?;
+    machine.halt_exit_code = exit_code;
+
+    let stop_reason = match input {
+        Some(input) => {
+            let input = fs::read(&input).map_err(|e| format!("failed to read {}: {}", input, e))?;
+            let io = ReadWriteIo::new(Cursor::new(input), io::stdout());
+            run_machine(&mut machine.with_io(io), dump_on_halt)
+        }
+        None => {
+            #[cfg(feature = "raw_terminal")]
+            let _raw_mode = raw_mode();
+            run_machine(&mut machine, dump_on_halt)
+        }
+    };
+
+    let message = match stop_reason {
+        StopReason::Halted { code } => process::exit(exit_status(code)),
+        StopReason::CycleLimit | StopReason::RegisterMatched | StopReason::Stopped => return Ok(()),
+        StopReason::InputExhausted => "program stopped after reading all of its input".to_string(),
+        StopReason::WriteLimitExceeded => "program wrote to memory too many times".to_string(),
+        StopReason::RecursionLimit => "program nested subroutine calls too deeply".to_string(),
+        StopReason::Timeout => "program ran out of time".to_string(),
+        StopReason::UninitRead(address) => {
+            format!("program read uninitialized memory at {:#06x}", address)
+        }
+        StopReason::UnexpectedR7Write(address) => {
+            format!("instruction at {:#06x} overwrote R7", address)
+        }
+        StopReason::Fault(address, e) => {
+            format!(
+                "instruction at {:#06x} couldn't be executed: {}",
+                address, e
+            )
+        }
     };
+    Err(message.into())
+}
 
-    let bytes: Vec<u8> = file.bytes().map(Result::unwrap).collect();
-    let mut machine = LC3::new(&bytes);
-    machine.run();
+/// Runs the program until it stops. Instructions that can't be executed stop it with
+/// `StopReason::Fault` instead of panicking.
+fn run_machine<I: Io>(machine: &mut LC3<I>, dump_on_halt: bool) -> StopReason {
+    let stop_reason = loop {
+        if let Some(stop_reason) = machine.try_step_n(GENERATOR_SLICE) {
+            break stop_reason;
+        }
+    };
+    if dump_on_halt {
+        if let StopReason::Halted { .. } = stop_reason {
+            dump_state(machine);
+        }
+    }
+    stop_reason
+}
+
+/// Switches the terminal to raw mode until the guard is dropped so GETC gets each key as soon as
+/// it's pressed. Does nothing if stdin isn't a terminal, and programs still run a line at a time
+/// if the terminal can't be switched.
+#[cfg(feature = "raw_terminal")]
+fn raw_mode() -> Option<RawModeGuard<Stty>> {
+    if !std::io::stdin().is_terminal() {
+        return None;
+    }
+    RawModeGuard::new(Stty::default()).ok()
+}
+
+/// Exit statuses only keep their low 8 bits, so R0 values that don't fit are reported as 255
+/// rather than wrapping, which would make e.g. 256 look like success
+fn exit_status(code: RegisterSize) -> i32 {
+    code.min(255) as i32
+}
+
+/// Prints the registers, PC, cond register and cycle count to stderr
+fn dump_state<I: Io>(machine: &LC3<I>) {
+    let registers: Vec<_> = machine
+        .registers
+        .iter()
+        .enumerate()
+        .map(|(i, value)| format!("R{}=x{:04X}", i, value))
+        .collect();
+    let cond = if machine.cond.contains(CondFlag::NEGATIVE) {
+        "N"
+    } else if machine.cond.contains(CondFlag::ZERO) {
+        "Z"
+    } else {
+        "P"
+    };
+    eprintln!("{}", registers.join(" "));
+    eprintln!(
+        "PC=x{:04X} COND={} CYCLES={}",
+        machine.pc, cond, machine.cycles
+    );
 }
@@ -0,0 +1,42 @@
+use super::{Memory, MemoryLocationSize, MAX_MEMORY_SIZE};
+
+/// `MemoryBacking` is where the machine's memory lives. Every access the machine makes to memory,
+/// including fetching instructions, goes through it so embedders can supply their own storage,
+/// e.g. memory mapped from a file or shared copy-on-write between machines.
+pub trait MemoryBacking {
+    fn read(&self, address: MemoryLocationSize) -> MemoryLocationSize;
+
+    fn write(&mut self, address: MemoryLocationSize, value: MemoryLocationSize);
+}
+
+impl MemoryBacking for Memory {
+    fn read(&self, address: MemoryLocationSize) -> MemoryLocationSize {
+        self[address as usize]
+    }
+
+    fn write(&mut self, address: MemoryLocationSize, value: MemoryLocationSize) {
+        self[address as usize] = value;
+    }
+}
+
+/// A set of memory locations stored as one bit per location
+#[derive(Debug, Clone)]
+pub(crate) struct AddressSet {
+    bits: Vec<u64>,
+}
+
+impl AddressSet {
+    pub(crate) fn new() -> Self {
+        AddressSet {
+            bits: vec![0; MAX_MEMORY_SIZE / 64],
+        }
+    }
+
+    pub(crate) fn insert(&mut self, address: MemoryLocationSize) {
+        self.bits[address as usize / 64] |= 1 << (address % 64);
+    }
+
+    pub(crate) fn contains(&self, address: MemoryLocationSize) -> bool {
+        self.bits[address as usize / 64] & (1 << (address % 64)) != 0
+    }
+}
@@ -0,0 +1,52 @@
+use std::convert::TryInto;
+
+use super::{io::BufferedIo, LoadError, StopReason, LC3};
+
+/// Everything needed to reproduce a run: the program's object file and all of the input it read.
+/// Record the input by running the program with an `io::RecordingIo`.
+///
+/// The machine has no other source of nondeterminism so replaying a session with the default
+/// machine settings reproduces the original run exactly.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Session {
+    pub program: Vec<u8>,
+    pub input: Vec<u8>,
+}
+
+impl Session {
+    /// Serializes the session as the program's length as a big endian u32, the program and then
+    /// the input
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = (self.program.len() as u32).to_be_bytes().to_vec();
+        bytes.extend_from_slice(&self.program);
+        bytes.extend_from_slice(&self.input);
+        bytes
+    }
+
+    /// Reads a session written by `to_bytes`. Returns `None` if `bytes` is too short to hold the
+    /// program it says it has.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        let (len, rest) = bytes.split_at(4.min(bytes.len()));
+        let len = u32::from_be_bytes(len.try_into().ok()?) as usize;
+        if rest.len() < len {
+            return None;
+        }
+
+        let (program, input) = rest.split_at(len);
+        Some(Session {
+            program: program.to_vec(),
+            input: input.to_vec(),
+        })
+    }
+}
+
+impl LC3 {
+    /// Runs the session's program with its recorded input and returns why it stopped along with
+    /// everything it wrote
+    pub fn replay(session: &Session) -> Result<(StopReason, Vec<u8>), LoadError> {
+        let mut machine =
+            LC3::from_bytes(&session.program)?.with_io(BufferedIo::new(&session.input));
+        let stop_reason = machine.run();
+        Ok((stop_reason, machine.io.output))
+    }
+}
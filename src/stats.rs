@@ -0,0 +1,55 @@
+use std::collections::HashMap;
+
+use super::{io::Io, memory::MemoryBacking, MemoryLocationSize, RegisterSize, LC3};
+
+/// How the branches a program executed went, see `LC3::collect_branch_stats`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BranchStats {
+    pub taken: u64,
+    pub not_taken: u64,
+    /// The number of times the branch at each address was executed
+    pub executions: HashMap<MemoryLocationSize, u64>,
+}
+
+impl BranchStats {
+    /// Returns up to `n` branch addresses with how many times they were executed, most executed
+    /// first. Ties are ordered by address.
+    pub fn hottest(&self, n: usize) -> Vec<(MemoryLocationSize, u64)> {
+        let mut branches: Vec<_> = self
+            .executions
+            .iter()
+            .map(|(&address, &count)| (address, count))
+            .collect();
+        branches.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        branches.truncate(n);
+        branches
+    }
+
+    pub(crate) fn record(&mut self, address: MemoryLocationSize, taken: bool) {
+        if taken {
+            self.taken += 1;
+        } else {
+            self.not_taken += 1;
+        }
+        *self.executions.entry(address).or_insert(0) += 1;
+    }
+}
+
+impl<I: Io, M: MemoryBacking> LC3<I, M> {
+    /// Returns the branch statistics collected so far
+    pub fn branch_stats(&self) -> BranchStats {
+        self.branch_stats.clone()
+    }
+
+    /// Returns the lowest value written to R6 while `track_stack_usage` was on, i.e. the furthest
+    /// the stack grew down, or `None` if R6 wasn't written
+    pub fn max_stack_usage(&self) -> Option<RegisterSize> {
+        self.lowest_stack_pointer
+    }
+
+    /// Returns the number of instructions executed plus the cycles they spent stalled on memory,
+    /// see `memory_stall_cycles`
+    pub fn cycle_cost(&self) -> u64 {
+        self.cycles + self.stall_cycles
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    io,
+    process::{Command, Stdio},
+};
+
+use super::{io::Io, memory::MemoryBacking, StopReason, LC3};
+
+/// A terminal that can be switched into raw mode, where each key press is passed to the program
+/// as soon as it's pressed instead of a line at a time and isn't echoed
+pub trait Terminal {
+    fn enter_raw_mode(&mut self) -> io::Result<()>;
+
+    /// Puts the terminal back the way it was before `enter_raw_mode`
+    fn restore(&mut self) -> io::Result<()>;
+}
+
+/// The terminal on the process's stdin, switched with the `stty` command. Only Unix-like systems
+/// have `stty`; elsewhere `enter_raw_mode` fails.
+#[derive(Debug, Default)]
+pub struct Stty {
+    /// The settings to restore, in the form `stty -g` prints them
+    saved: Option<String>,
+}
+
+impl Terminal for Stty {
+    fn enter_raw_mode(&mut self) -> io::Result<()> {
+        let saved = stty(&["-g"])?;
+        // Leaves output processing and signals alone so newlines and Ctrl-C still work
+        stty(&["-icanon", "-echo", "min", "1"])?;
+        self.saved = Some(saved.trim().to_string());
+        Ok(())
+    }
+
+    fn restore(&mut self) -> io::Result<()> {
+        match self.saved.take() {
+            Some(saved) => stty(&[&saved]).map(|_| ()),
+            None => Ok(()),
+        }
+    }
+}
+
+fn stty(args: &[&str]) -> io::Result<String> {
+    let output = Command::new("stty")
+        .args(args)
+        .stdin(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        let message = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(io::Error::other(message));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Keeps a terminal in raw mode until it's dropped, which restores it even when unwinding from a
+/// panic
+pub struct RawModeGuard<T: Terminal> {
+    terminal: T,
+}
+
+impl<T: Terminal> RawModeGuard<T> {
+    pub fn new(mut terminal: T) -> io::Result<Self> {
+        terminal.enter_raw_mode()?;
+        Ok(RawModeGuard { terminal })
+    }
+}
+
+impl<T: Terminal> Drop for RawModeGuard<T> {
+    fn drop(&mut self) {
+        // There's nowhere to report the error and leaving the terminal raw is the best we can do
+        let _ = self.terminal.restore();
+    }
+}
+
+impl<I: Io, M: MemoryBacking> LC3<I, M> {
+    /// Runs like `run` with `terminal` in raw mode so GETC and IN get each key as soon as it's
+    /// pressed, e.g. for games. The terminal is restored when the run ends, even if it panics.
+    pub fn run_with_raw_terminal<T: Terminal>(&mut self, terminal: T) -> io::Result<StopReason> {
+        let _guard = RawModeGuard::new(terminal)?;
+        Ok(self.run())
+    }
+}
@@ -0,0 +1,108 @@
+use std::{error::Error, fmt, io::Write};
+
+use super::{
+    disassembler::disassemble, io::Io, memory::MemoryBacking, InstructionSize, MemoryLocationSize,
+    LC3,
+};
+
+/// An instruction as it was about to be executed
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TraceEntry {
+    pub pc: MemoryLocationSize,
+    pub instruction: InstructionSize,
+}
+
+/// The first point where execution diverged from a golden trace
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct TraceMismatch {
+    /// Index into the golden trace of the step that diverged
+    pub step: usize,
+    pub expected: TraceEntry,
+    /// What was about to execute instead, or `None` if the program had already stopped
+    pub actual: Option<TraceEntry>,
+}
+
+impl fmt::Display for TraceMismatch {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "step {} expected {:#06x} at {:#06x} but ",
+            self.step, self.expected.instruction, self.expected.pc
+        )?;
+        match self.actual {
+            Some(actual) => write!(f, "got {:#06x} at {:#06x}", actual.instruction, actual.pc),
+            None => write!(f, "the program had stopped"),
+        }
+    }
+}
+
+impl Error for TraceMismatch {}
+
+impl<I: Io, M: MemoryBacking> LC3<I, M> {
+    /// Steps through the program once for each entry in `golden`, checking that the instruction
+    /// about to execute matches the entry. Stops at the first divergence.
+    pub fn run_with_golden(&mut self, golden: &[TraceEntry]) -> Result<(), TraceMismatch> {
+        self.start();
+        for (step, &expected) in golden.iter().enumerate() {
+            let actual = if self.running {
+                Some(TraceEntry {
+                    pc: self.pc,
+                    instruction: self.memory.read(self.pc),
+                })
+            } else {
+                None
+            };
+
+            if actual != Some(expected) {
+                return Err(TraceMismatch {
+                    step,
+                    expected,
+                    actual,
+                });
+            }
+
+            self.step_unchecked();
+        }
+
+        Ok(())
+    }
+
+    /// Writes a line to `writer` for every instruction executed from now on, until `stop_trace`
+    /// is called. Each line has the instruction's cycle (the number of instructions executed
+    /// before it), its address, the instruction word and its disassembly, e.g.
+    ///
+    /// ```text
+    /// 0 x3000 x1261 ADD R1, R1, #1
+    /// ```
+    ///
+    /// Tracing stops if a write fails.
+    pub fn start_trace<W: Write + Send + 'static>(&mut self, writer: W) {
+        self.trace_writer = Some(Box::new(writer));
+    }
+
+    /// Stops tracing and returns the writer the trace was being written to
+    pub fn stop_trace(&mut self) -> Option<Box<dyn Write + Send>> {
+        self.trace_writer.take()
+    }
+
+    pub(crate) fn write_trace(
+        &mut self,
+        cycle: u64,
+        pc: MemoryLocationSize,
+        instr: InstructionSize,
+    ) {
+        if let Some(writer) = &mut self.trace_writer {
+            let written = writeln!(
+                writer,
+                "{} x{:04X} x{:04X} {}",
+                cycle,
+                pc,
+                instr,
+                disassemble(instr, pc)
+            );
+            if written.is_err() {
+                self.trace_writer = None;
+            }
+        }
+    }
+}
@@ -0,0 +1,104 @@
+use lilc3::{
+    instruction::{AddImmediate, Branch, Instruction, Jump, JumpSubRoutineOffset, Trap, TrapCode},
+    io::BufferedIo,
+    CondFlag, LC3, MAX_MEMORY_SIZE, PROGRAM_START,
+};
+
+fn machine(program: &[Instruction]) -> LC3<BufferedIo> {
+    let words: Vec<u16> = program.iter().map(|i| u16::from_be(i.encode())).collect();
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(BufferedIo::default());
+    machine.load_words_at(PROGRAM_START, &words).unwrap();
+    machine
+}
+
+fn add() -> Instruction {
+    Instruction::AddImmediate(AddImmediate {
+        dr: 0,
+        sr1: 0,
+        imm5: 1,
+    })
+}
+
+fn halt() -> Instruction {
+    Instruction::Trap(Trap {
+        vect8: TrapCode::Halt,
+    })
+}
+
+#[test]
+fn unreachable_block_excluded() {
+    let machine = machine(&[
+        Instruction::Branch(Branch {
+            nzp: CondFlag::all(),
+            pc_offset9: 2,
+        }),
+        add(), // skipped by the branch
+        add(),
+        Instruction::JumpSubRoutineOffset(JumpSubRoutineOffset { pc_offset11: 1 }),
+        halt(),
+        Instruction::Jump(Jump { base_r: 7 }),
+    ]);
+
+    let mut reachable: Vec<_> = machine.reachable_from(PROGRAM_START).into_iter().collect();
+    reachable.sort_unstable();
+
+    assert_eq!(
+        reachable,
+        vec![
+            PROGRAM_START,
+            PROGRAM_START + 3,
+            PROGRAM_START + 4,
+            PROGRAM_START + 5
+        ]
+    );
+}
+
+#[test]
+fn conditional_branch_follows_both_paths() {
+    let machine = machine(&[
+        Instruction::Branch(Branch {
+            nzp: CondFlag::ZERO,
+            pc_offset9: 1,
+        }),
+        add(),
+        halt(),
+        add(), // after HALT
+    ]);
+
+    assert_eq!(machine.reachable_from(PROGRAM_START).len(), 3);
+}
+
+#[test]
+fn infinite_loop_has_no_reachable_halt() {
+    let machine = machine(&[
+        add(),
+        Instruction::Branch(Branch {
+            nzp: CondFlag::all(),
+            pc_offset9: 0xFFFE,
+        }),
+        halt(), // never reached
+    ]);
+
+    assert!(!machine.has_reachable_halt(PROGRAM_START));
+    assert!(machine.has_reachable_halt(PROGRAM_START + 2));
+}
+
+#[test]
+fn trap_table_missing_without_os() {
+    let mut machine = machine(&[halt()]);
+
+    assert_eq!(
+        machine.validate_trap_table(),
+        vec![
+            TrapCode::GetC,
+            TrapCode::Out,
+            TrapCode::Puts,
+            TrapCode::In,
+            TrapCode::PutsP,
+            TrapCode::Halt
+        ]
+    );
+
+    machine.load_words_at(0x0025, &[0x0400]).unwrap();
+    assert!(!machine.validate_trap_table().contains(&TrapCode::Halt));
+}
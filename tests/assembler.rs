@@ -0,0 +1,13 @@
+use lilc3::assembler::{fill, stringz};
+
+#[test]
+fn fill_negative() {
+    assert_eq!(fill(-1), 0xFFFF);
+    assert_eq!(fill(-32768), 0x8000);
+}
+
+#[test]
+fn stringz_null_terminated() {
+    assert_eq!(stringz("hi"), vec![b'h' as u16, b'i' as u16, 0]);
+    assert_eq!(stringz(""), vec![0]);
+}
@@ -0,0 +1,206 @@
+use std::{env, fs, path::PathBuf, process::Command};
+
+use lilc3::{
+    instruction::{AddImmediate, Instruction, Load, Trap, TrapCode},
+    PROGRAM_START,
+};
+
+/// Writes `contents` to a file in the temp directory unique to this test
+fn temp_file(name: &str, contents: &[u8]) -> PathBuf {
+    let path = env::temp_dir().join(format!("lilc3-{}-{}", std::process::id(), name));
+    fs::write(&path, contents).unwrap();
+    path
+}
+
+fn object_file(program: &[Instruction]) -> Vec<u8> {
+    let mut bytes = PROGRAM_START.to_be_bytes().to_vec();
+    for instr in program {
+        bytes.extend_from_slice(&u16::from_be(instr.encode()).to_be_bytes());
+    }
+    bytes
+}
+
+fn trap(vect8: TrapCode) -> Instruction {
+    Instruction::Trap(Trap { vect8 })
+}
+
+#[test]
+fn input_file() {
+    let program = object_file(&[
+        trap(TrapCode::GetC),
+        trap(TrapCode::Out),
+        trap(TrapCode::GetC),
+        trap(TrapCode::Out),
+        trap(TrapCode::Halt),
+    ]);
+    let program = temp_file("input_file.obj", &program);
+    let input = temp_file("input_file.txt", b"hi");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lilc3"))
+        .arg("--input")
+        .arg(&input)
+        .arg(&program)
+        .output()
+        .unwrap();
+    fs::remove_file(program).unwrap();
+    fs::remove_file(input).unwrap();
+
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"hiHALT\n");
+}
+
+#[test]
+fn exit_code() {
+    let program = object_file(&[
+        Instruction::AddImmediate(AddImmediate {
+            dr: 0,
+            sr1: 0,
+            imm5: 5,
+        }),
+        trap(TrapCode::Halt),
+    ]);
+    let program = temp_file("exit_code.obj", &program);
+
+    let with_flag = Command::new(env!("CARGO_BIN_EXE_lilc3"))
+        .arg("--exit-code")
+        .arg(&program)
+        .output()
+        .unwrap();
+    let without_flag = Command::new(env!("CARGO_BIN_EXE_lilc3"))
+        .arg(&program)
+        .output()
+        .unwrap();
+    fs::remove_file(program).unwrap();
+
+    assert_eq!(with_flag.status.code(), Some(5));
+    assert_eq!(without_flag.status.code(), Some(0));
+}
+
+#[test]
+fn exit_code_too_large_for_status() {
+    let mut program = object_file(&[
+        Instruction::Load(Load {
+            dr: 0,
+            pc_offset9: 1,
+        }),
+        trap(TrapCode::Halt),
+    ]);
+    program.extend_from_slice(&256u16.to_be_bytes());
+    let program = temp_file("exit_code_too_large.obj", &program);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lilc3"))
+        .arg("--exit-code")
+        .arg(&program)
+        .output()
+        .unwrap();
+    fs::remove_file(program).unwrap();
+
+    assert_eq!(output.status.code(), Some(255));
+}
+
+#[test]
+fn missing_program_file() {
+    let path = env::temp_dir().join(format!("lilc3-{}-missing.obj", std::process::id()));
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lilc3"))
+        .arg(&path)
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with(&format!("lilc3: failed to read {}: ", path.display())));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn no_program_given() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lilc3")).output().unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("usage: lilc3"));
+}
+
+#[test]
+fn dump_on_halt() {
+    let program = object_file(&[
+        Instruction::AddImmediate(AddImmediate {
+            dr: 2,
+            sr1: 2,
+            imm5: 5,
+        }),
+        trap(TrapCode::Halt),
+    ]);
+    let program = temp_file("dump_on_halt.obj", &program);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lilc3"))
+        .arg("--dump-on-halt")
+        .arg(&program)
+        .output()
+        .unwrap();
+    fs::remove_file(&program).unwrap();
+
+    assert_eq!(output.stdout, b"HALT\n");
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "R0=x0000 R1=x0000 R2=x0005 R3=x0000 R4=x0000 R5=x0000 R6=x0000 R7=x0000\n\
+         PC=x3002 COND=P CYCLES=2\n"
+    );
+}
+
+#[test]
+fn reserved_opcode_is_an_error() {
+    let mut program = object_file(&[trap(TrapCode::Out)]);
+    program.extend_from_slice(&[0xD0, 0x00]);
+    let program = temp_file("reserved_opcode.obj", &program);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lilc3"))
+        .arg(&program)
+        .output()
+        .unwrap();
+    fs::remove_file(&program).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("lilc3: instruction at 0x3001 couldn't be executed: "));
+    assert!(!stderr.contains("panicked"));
+}
+
+#[test]
+fn input_exhausted_is_an_error() {
+    let program = temp_file(
+        "input_exhausted.obj",
+        &object_file(&[trap(TrapCode::GetC), trap(TrapCode::Halt)]),
+    );
+    let input = temp_file("input_exhausted.txt", b"");
+
+    let output = Command::new(env!("CARGO_BIN_EXE_lilc3"))
+        .arg("--input")
+        .arg(&input)
+        .arg(&program)
+        .output()
+        .unwrap();
+    fs::remove_file(program).unwrap();
+    fs::remove_file(input).unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8(output.stderr).unwrap(),
+        "lilc3: program stopped after reading all of its input\n"
+    );
+}
+
+#[test]
+fn unknown_option_rejected() {
+    let output = Command::new(env!("CARGO_BIN_EXE_lilc3"))
+        .arg("--dump-on-hlat")
+        .arg("prog.obj")
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("lilc3: unknown option --dump-on-hlat\n"));
+    assert!(stderr.contains("usage: lilc3"));
+}
@@ -0,0 +1,34 @@
+use std::{thread, time::Duration};
+
+use lilc3::{
+    instruction::{Branch, Instruction},
+    io::BufferedIo,
+    CondFlag, StopReason, LC3, MAX_MEMORY_SIZE, PROGRAM_START,
+};
+
+#[test]
+fn stop_from_another_thread() {
+    let spin = Instruction::Branch(Branch {
+        nzp: CondFlag::all(),
+        pc_offset9: 0xFFFF,
+    });
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(BufferedIo::default());
+    machine
+        .load_words_at(PROGRAM_START, &[u16::from_be(spin.encode())])
+        .unwrap();
+    let control = machine.run_control();
+
+    let worker = thread::spawn(move || {
+        let stop_reason = machine.run();
+        (stop_reason, machine.cycles, machine.pc)
+    });
+    while control.cycles() < 1000 {
+        thread::sleep(Duration::from_millis(1));
+    }
+    control.request_stop();
+    let (stop_reason, cycles, pc) = worker.join().unwrap();
+
+    assert_eq!(stop_reason, StopReason::Stopped);
+    assert!(cycles >= 1000);
+    assert_eq!(pc, PROGRAM_START);
+}
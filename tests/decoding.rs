@@ -1,6 +1,10 @@
 use lilc3::{
-    instruction::{AddRegister, Instruction},
-    CondFlag, LC3,
+    instruction::{
+        decode_program, instructions_equivalent, AddImmediate, AddRegister, DecodeError,
+        DecodeMode, Instruction, JumpSubRoutineOffset, JumpSubRoutineRegister, Not, Trap, TrapCode,
+    },
+    io::BufferedIo,
+    CondFlag, StepError, StopReason, Warning, LC3, MAX_MEMORY_SIZE, PROGRAM_START,
 };
 
 #[test]
@@ -19,8 +23,217 @@ fn decoding() {
     let mut machine = LC3::new(&instructions);
     machine.registers[sr1 as usize] = 5;
     machine.registers[sr2 as usize] = 6;
-    machine.step();
+    machine.step().unwrap();
 
     assert_eq!(machine.registers[dr as usize], 11);
     assert_eq!(machine.cond, CondFlag::POSITIVE);
 }
+
+#[test]
+fn not_well_formed() {
+    let not = Not { dr: 1, sr1: 2 };
+    let instr = u16::from_be(Instruction::Not(not).encode());
+
+    assert_eq!(
+        Instruction::try_decode(instr, DecodeMode::Strict),
+        Ok(Instruction::Not(not))
+    );
+    assert_eq!(
+        Instruction::try_decode(instr, DecodeMode::Lenient),
+        Ok(Instruction::Not(not))
+    );
+}
+
+#[test]
+fn not_malformed() {
+    let not = Not { dr: 1, sr1: 2 };
+    // Clear the low bits which the spec requires to be set
+    let instr = u16::from_be(Instruction::Not(not).encode()) & !0x3F;
+
+    assert_eq!(
+        Instruction::try_decode(instr, DecodeMode::Strict),
+        Err(DecodeError::MalformedInstruction(instr))
+    );
+    assert_eq!(
+        Instruction::try_decode(instr, DecodeMode::Lenient),
+        Ok(Instruction::Not(not))
+    );
+}
+
+#[test]
+fn jump_subroutine_offset_round_trip() {
+    // Offsets with bits 6 to 8 set look like a base register if the mode bit is ignored
+    for &pc_offset11 in &[0, 10, 0x1C0, 0x3FF, 0xFFFF, 0xFC00] {
+        let jsr = Instruction::JumpSubRoutineOffset(JumpSubRoutineOffset { pc_offset11 });
+        let instr = u16::from_be(jsr.encode());
+
+        assert_eq!(instr >> 11 & 1, 1);
+        assert_eq!(Instruction::decode(instr), jsr);
+    }
+}
+
+#[test]
+fn jump_subroutine_register_round_trip() {
+    for base_r in 0..8 {
+        let jsrr = Instruction::JumpSubRoutineRegister(JumpSubRoutineRegister { base_r });
+        let instr = u16::from_be(jsrr.encode());
+
+        assert_eq!(instr >> 11 & 1, 0);
+        assert_eq!(Instruction::decode(instr), jsrr);
+    }
+}
+
+#[test]
+fn decode_program_words() {
+    let add = Instruction::AddRegister(AddRegister {
+        dr: 1,
+        sr1: 2,
+        sr2: 3,
+    });
+    let halt = Instruction::Trap(Trap {
+        vect8: TrapCode::Halt,
+    });
+    let reserved = 0xD000;
+    let unknown_trap = 0xF0FF;
+
+    let words = [
+        u16::from_be(add.encode()),
+        reserved,
+        unknown_trap,
+        u16::from_be(halt.encode()),
+    ];
+
+    assert_eq!(
+        decode_program(&words),
+        vec![
+            Ok(add),
+            Err(DecodeError::ReservedOpCode(reserved)),
+            Err(DecodeError::UnknownTrap(unknown_trap)),
+            Ok(halt),
+        ]
+    );
+}
+
+#[test]
+fn skip_decode_errors() {
+    let add = u16::from_be(
+        Instruction::AddImmediate(AddImmediate {
+            dr: 1,
+            sr1: 1,
+            imm5: 1,
+        })
+        .encode(),
+    );
+    let halt = u16::from_be(
+        Instruction::Trap(Trap {
+            vect8: TrapCode::Halt,
+        })
+        .encode(),
+    );
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(BufferedIo::default());
+    machine
+        .load_words_at(PROGRAM_START, &[add, 0xD000, add, halt])
+        .unwrap();
+    machine.skip_decode_errors = true;
+
+    assert_eq!(machine.run(), StopReason::Halted { code: 0 });
+    assert_eq!(machine.registers[1], 2);
+    assert_eq!(
+        machine.warnings,
+        vec![Warning::SkippedWord(PROGRAM_START + 1, 0xD000)]
+    );
+
+    machine.pc = PROGRAM_START + 1;
+    assert_eq!(
+        machine.step(),
+        Err(StepError::Decode(DecodeError::ReservedOpCode(0xD000)))
+    );
+    assert_eq!(machine.pc, PROGRAM_START + 2);
+}
+
+#[test]
+fn equivalent_ignoring_unused_bits() {
+    // NOT R1, R2 with and without the low bits set
+    assert!(instructions_equivalent(0x92BF, 0x9280));
+    // JMP R3 with junk in the unused bits
+    assert!(instructions_equivalent(0xC0C0, 0xCEFF));
+    // ADD R1, R2, R3 with bits 3 and 4 set
+    assert!(instructions_equivalent(0x1283, 0x129B));
+}
+
+#[test]
+fn not_equivalent() {
+    // ADD R1, R2, R3 and ADD R1, R2, R4
+    assert!(!instructions_equivalent(0x1283, 0x1284));
+    // Reserved opcodes only match themselves
+    assert!(instructions_equivalent(0xD000, 0xD000));
+    assert!(!instructions_equivalent(0xD000, 0xD001));
+}
+
+#[test]
+fn register_mode_strict() {
+    // ADD R1, R2, R3
+    let clean = 0x1283;
+    assert_eq!(
+        Instruction::try_decode(clean, DecodeMode::Strict),
+        Ok(Instruction::AddRegister(AddRegister {
+            dr: 1,
+            sr1: 2,
+            sr2: 3
+        }))
+    );
+
+    // The same with bit 3 set, and AND R1, R2, R3 with bit 4 set
+    for &malformed in &[0x128B, 0x5293] {
+        assert_eq!(
+            Instruction::try_decode(malformed, DecodeMode::Strict),
+            Err(DecodeError::MalformedInstruction(malformed))
+        );
+        assert!(Instruction::try_decode(malformed, DecodeMode::Lenient).is_ok());
+    }
+}
+
+#[test]
+fn register_mode_strict_run() {
+    let halt = u16::from_be(
+        Instruction::Trap(Trap {
+            vect8: TrapCode::Halt,
+        })
+        .encode(),
+    );
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(BufferedIo::default());
+    machine
+        .load_words_at(PROGRAM_START, &[0x128B, halt])
+        .unwrap();
+    machine.decode_mode = DecodeMode::Strict;
+    machine.skip_decode_errors = true;
+    machine.run();
+
+    // Fast dispatch would have executed the malformed ADD without decoding it
+    assert_eq!(
+        machine.warnings,
+        vec![Warning::SkippedWord(PROGRAM_START, 0x128B)]
+    );
+}
+
+#[test]
+fn unknown_trap_ignored_with_warning() {
+    let halt = u16::from_be(
+        Instruction::Trap(Trap {
+            vect8: TrapCode::Halt,
+        })
+        .encode(),
+    );
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(BufferedIo::default());
+    machine
+        .load_words_at(PROGRAM_START, &[0xF030, halt])
+        .unwrap();
+    machine.ignore_unknown_traps = true;
+
+    assert_eq!(machine.run(), StopReason::Halted { code: 0 });
+    assert_eq!(machine.cycles, 2);
+    assert_eq!(
+        machine.warnings,
+        vec![Warning::UnknownTrap(PROGRAM_START, 0x30)]
+    );
+}
@@ -0,0 +1,76 @@
+use lilc3::{
+    disassembler::{disassemble, disassemble_structured, DisasmLine, Operand},
+    instruction::{Instruction, Load, LoadBaseOffset, Trap, TrapCode},
+};
+
+fn word(instr: Instruction) -> u16 {
+    u16::from_be(instr.encode())
+}
+
+#[test]
+fn load() {
+    let ld = word(Instruction::Load(Load {
+        dr: 2,
+        pc_offset9: 5,
+    }));
+
+    let line = disassemble_structured(ld, 0x3000);
+
+    assert_eq!(
+        line,
+        DisasmLine {
+            mnemonic: "LD".to_string(),
+            operands: vec![Operand::Register(2), Operand::Address(0x3006)],
+            comment: None,
+        }
+    );
+    assert_eq!(line.to_string(), "LD R2, x3006");
+}
+
+#[test]
+fn branch_backwards() {
+    // BRnp #-3
+    let line = disassemble_structured(0x0BFD, 0x3010);
+
+    assert_eq!(line.mnemonic, "BRnp");
+    assert_eq!(line.operands, vec![Operand::Address(0x300E)]);
+    assert_eq!(line.comment, None);
+}
+
+#[test]
+fn branch_condition_letters() {
+    assert_eq!(disassemble_structured(0x0800, 0x3000).mnemonic, "BRn");
+    assert_eq!(disassemble_structured(0x0400, 0x3000).mnemonic, "BRz");
+    assert_eq!(disassemble_structured(0x0200, 0x3000).mnemonic, "BRp");
+    assert_eq!(disassemble_structured(0x0C00, 0x3000).mnemonic, "BRnz");
+}
+
+#[test]
+fn negative_base_offset() {
+    let ldr = word(Instruction::LoadBaseOffset(LoadBaseOffset {
+        dr: 0,
+        base_r: 6,
+        pc_offset6: 0x3F,
+    }));
+
+    assert_eq!(disassemble(ldr, 0x3000), "LDR R0, R6, #-1");
+}
+
+#[test]
+fn reserved_opcode() {
+    let line = disassemble_structured(0xD000, 0x3000);
+
+    assert_eq!(line.mnemonic, ".FILL");
+    assert_eq!(line.operands, vec![Operand::Immediate(0xD000u16 as i16)]);
+    assert!(line.comment.is_some());
+}
+
+#[test]
+fn trap_by_name() {
+    let getc = word(Instruction::Trap(Trap {
+        vect8: TrapCode::GetC,
+    }));
+
+    assert_eq!(getc, 0xF020);
+    assert_eq!(disassemble(getc, 0x3000), "GETC");
+}
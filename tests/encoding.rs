@@ -0,0 +1,134 @@
+use lilc3::{
+    instruction::{
+        decode_program, encode_program, AddImmediate, AddRegister, AndImmediate, AndRegister,
+        Branch, DecodeMode, EncodeError, Instruction, JumpSubRoutineOffset, Load, Trap, TrapCode,
+    },
+    CondFlag,
+};
+
+#[test]
+fn branch_forward() {
+    let branch = Branch::to_target(CondFlag::ZERO, 0x3000, 0x3010).unwrap();
+
+    assert_eq!(branch.pc_offset9, 15);
+}
+
+#[test]
+fn branch_backward() {
+    let branch = Branch::to_target(CondFlag::all(), 0x3010, 0x3000).unwrap();
+
+    assert_eq!(branch.pc_offset9, (-17i16) as u16);
+    // Round trips through the encoding
+    let word = u16::from_be(Instruction::Branch(branch).encode());
+    assert_eq!(Instruction::decode(word), Instruction::Branch(branch));
+}
+
+#[test]
+fn branch_out_of_range() {
+    assert_eq!(
+        Branch::to_target(CondFlag::all(), 0x3000, 0x3101),
+        Err(EncodeError::OffsetOutOfRange {
+            distance: 256,
+            bits: 9
+        })
+    );
+    assert!(Branch::to_target(CondFlag::all(), 0x3000, 0x3100).is_ok());
+    assert!(Branch::to_target(CondFlag::all(), 0x3000, 0x2F01).is_ok());
+    assert!(Branch::to_target(CondFlag::all(), 0x3000, 0x2F00).is_err());
+}
+
+#[test]
+fn load_and_jsr_targets() {
+    let load = Load::to_target(3, 0x3000, 0x2FFF).unwrap();
+    assert_eq!(load.dr, 3);
+    assert_eq!(load.pc_offset9, (-2i16) as u16);
+
+    let jsr = JumpSubRoutineOffset::to_target(0x3000, 0x3400).unwrap();
+    assert_eq!(jsr.pc_offset11, 0x3FF);
+    assert!(JumpSubRoutineOffset::to_target(0x3000, 0x3401).is_err());
+}
+
+#[test]
+fn encode_program_round_trip() {
+    let program = vec![
+        Instruction::AddImmediate(AddImmediate {
+            dr: 1,
+            sr1: 1,
+            imm5: 1,
+        }),
+        Instruction::Branch(Branch {
+            nzp: CondFlag::POSITIVE,
+            pc_offset9: 0xFFFE,
+        }),
+        Instruction::Trap(Trap {
+            vect8: TrapCode::Halt,
+        }),
+    ];
+
+    let words = encode_program(&program);
+
+    assert_eq!(words, vec![0x1261, 0x03FE, 0xF025]);
+    let decoded: Vec<_> = decode_program(&words)
+        .into_iter()
+        .map(Result::unwrap)
+        .collect();
+    assert_eq!(decoded, program);
+}
+
+const IMMEDIATE_MODE_BIT: u16 = 1 << 5;
+
+#[test]
+fn immediate_forms_set_mode_bit() {
+    let immediates = [
+        Instruction::AddImmediate(AddImmediate {
+            dr: 7,
+            sr1: 7,
+            imm5: 0,
+        }),
+        Instruction::AndImmediate(AndImmediate {
+            dr: 0,
+            sr1: 0,
+            imm5: 0xFFFF,
+        }),
+    ];
+
+    for &instr in &immediates {
+        let word = u16::from_be(instr.encode());
+        assert_eq!(word & IMMEDIATE_MODE_BIT, IMMEDIATE_MODE_BIT);
+        assert_eq!(Instruction::try_decode(word, DecodeMode::Strict), Ok(instr));
+    }
+}
+
+#[test]
+fn register_forms_clear_mode_bit() {
+    let registers = [
+        Instruction::AddRegister(AddRegister {
+            dr: 7,
+            sr1: 7,
+            sr2: 7,
+        }),
+        Instruction::AndRegister(AndRegister {
+            dr: 0,
+            sr1: 0,
+            sr2: 0,
+        }),
+    ];
+
+    for &instr in &registers {
+        let word = u16::from_be(instr.encode());
+        assert_eq!(word & IMMEDIATE_MODE_BIT, 0);
+        assert_eq!(Instruction::try_decode(word, DecodeMode::Strict), Ok(instr));
+    }
+}
+
+#[cfg(debug_assertions)]
+#[test]
+#[should_panic(expected = "sr2 spilled into the mode bit")]
+fn out_of_range_sr2_caught() {
+    Instruction::AddRegister(AddRegister {
+        dr: 0,
+        sr1: 0,
+        sr2: 0x20,
+    })
+    .encode();
+}
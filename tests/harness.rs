@@ -0,0 +1,225 @@
+use lilc3::{
+    harness::{behaviorally_equivalent, run_test_case},
+    instruction::{
+        AddImmediate, AndImmediate, Branch, DecodeError, Instruction, LoadEffectiveAddress, Store,
+        Trap, TrapCode,
+    },
+    lc3_asm, CondFlag, StepError, StopReason, LC3, PROGRAM_START,
+};
+
+fn object_file(program: &[Instruction]) -> Vec<u8> {
+    let mut bytes = PROGRAM_START.to_be_bytes().to_vec();
+    for instr in program {
+        bytes.extend_from_slice(&u16::from_be(instr.encode()).to_be_bytes());
+    }
+    bytes
+}
+
+fn trap(vect8: TrapCode) -> Instruction {
+    Instruction::Trap(Trap { vect8 })
+}
+
+#[test]
+fn echo() {
+    let program = object_file(&[
+        trap(TrapCode::GetC),
+        trap(TrapCode::Out),
+        trap(TrapCode::GetC),
+        trap(TrapCode::Out),
+        trap(TrapCode::Halt),
+    ]);
+
+    let result = run_test_case(&program, b"ok", 100).unwrap();
+
+    assert_eq!(result.output, b"okHALT\n");
+    assert_eq!(result.registers[0], b'k' as u16);
+    assert_eq!(result.stop_reason, StopReason::Halted { code: 0 });
+    assert_eq!(result.cycles, 5);
+}
+
+#[test]
+fn cycle_limit() {
+    let program = object_file(&[Instruction::Branch(Branch {
+        nzp: CondFlag::all(),
+        pc_offset9: 0xFFFF, // negative one
+    })]);
+
+    let result = run_test_case(&program, b"", 100).unwrap();
+
+    assert_eq!(result.stop_reason, StopReason::CycleLimit);
+    assert_eq!(result.cycles, 100);
+}
+
+#[test]
+fn malformed_program_faults() {
+    let mut program = object_file(&[trap(TrapCode::Out)]);
+    program.extend_from_slice(&[0xD0, 0x00]);
+
+    let result = run_test_case(&program, b"", 100).unwrap();
+
+    assert_eq!(
+        result.stop_reason,
+        StopReason::Fault(
+            PROGRAM_START + 1,
+            StepError::Decode(DecodeError::ReservedOpCode(0xD000))
+        )
+    );
+    assert_eq!(result.output, b"\0");
+    assert_eq!(result.cycles, 1);
+
+    let extended = object_file(&[trap(TrapCode::Gets)]);
+    let result = run_test_case(&extended, b"", 100).unwrap();
+    assert_eq!(
+        result.stop_reason,
+        StopReason::Fault(
+            PROGRAM_START,
+            StepError::ExtendedTrapDisabled(TrapCode::Gets)
+        )
+    );
+}
+
+#[test]
+fn input_exhausted() {
+    let program = object_file(&[trap(TrapCode::GetC), trap(TrapCode::Halt)]);
+
+    let result = run_test_case(&program, b"", 100).unwrap();
+
+    assert_eq!(result.stop_reason, StopReason::InputExhausted);
+    assert_eq!(result.output, b"");
+}
+
+#[test]
+fn states_differing_only_in_memory() {
+    let program = object_file(&[
+        Instruction::Store(Store {
+            sr: 0,
+            pc_offset9: 5,
+        }),
+        trap(TrapCode::Halt),
+    ]);
+    let mut scratch = LC3::new(&program);
+    scratch.registers[0] = 42;
+    let mut reference = LC3::new(&program);
+    reference.registers[0] = 42;
+
+    scratch.step().unwrap();
+    reference.pc += 1;
+    let (scratch, reference) = (scratch.state(), reference.state());
+
+    assert_ne!(scratch, reference);
+    assert!(scratch.state_eq_ignoring_memory(&reference));
+}
+
+#[test]
+fn program_hash() {
+    let program = object_file(&[trap(TrapCode::Out), trap(TrapCode::Halt)]);
+    let range = PROGRAM_START..PROGRAM_START + 2;
+
+    let a = LC3::new(&program);
+    let b = LC3::new(&program);
+    assert_eq!(a.program_hash(range.clone()), b.program_hash(range.clone()));
+
+    let changed = object_file(&[trap(TrapCode::Puts), trap(TrapCode::Halt)]);
+    let changed = LC3::new(&changed);
+    assert_ne!(a.program_hash(range.clone()), changed.program_hash(range));
+}
+
+#[test]
+fn program_hash_is_fnv1a() {
+    let machine = LC3::new(&object_file(&[]));
+
+    // The FNV-1a hash of no bytes is the offset basis and of two zero bytes is fixed
+    assert_eq!(machine.program_hash(0..0), 0xcbf2_9ce4_8422_2325);
+    assert_eq!(machine.program_hash(0..1), 0x0832_8807_b4eb_6fed);
+}
+
+#[test]
+fn lc3_asm_builds_runnable_machine() {
+    let mut machine = lc3_asm![
+        AddRegister {
+            dr: 1,
+            sr1: 2,
+            sr2: 3
+        },
+        Trap { vect8: Halt },
+    ];
+    machine.registers[2] = 5;
+    machine.registers[3] = 6;
+
+    assert_eq!(machine.run(), StopReason::Halted { code: 0 });
+    assert_eq!(machine.registers[1], 11);
+    assert_eq!(machine.cycles, 2);
+}
+
+#[test]
+fn lc3_asm_accepts_qualified_trap_codes() {
+    let machine = lc3_asm![Trap {
+        vect8: TrapCode::Out
+    }];
+
+    assert_eq!(machine.memory[PROGRAM_START as usize], 0xF021);
+}
+
+/// Reads a character and echoes the one after it
+fn echo_next(imm5: u16) -> Vec<u8> {
+    object_file(&[
+        trap(TrapCode::GetC),
+        Instruction::AddImmediate(AddImmediate {
+            dr: 0,
+            sr1: 0,
+            imm5,
+        }),
+        trap(TrapCode::Out),
+        trap(TrapCode::Halt),
+    ])
+}
+
+#[test]
+fn behavioral_equivalence() {
+    let reference = echo_next(1);
+
+    assert!(behaviorally_equivalent(
+        &reference,
+        &echo_next(1),
+        b"a",
+        100
+    ));
+    assert!(!behaviorally_equivalent(
+        &reference,
+        &echo_next(2),
+        b"a",
+        100
+    ));
+    assert!(!behaviorally_equivalent(&reference, &[0x30], b"a", 100));
+}
+
+#[test]
+fn halting_not_equivalent_to_looping() {
+    let halts = object_file(&[trap(TrapCode::Halt)]);
+    // Prints the same message HALT does, clears R0 again and spins
+    let mut loops = object_file(&[
+        Instruction::LoadEffectiveAddress(LoadEffectiveAddress {
+            dr: 0,
+            pc_offset9: 3,
+        }),
+        trap(TrapCode::Puts),
+        Instruction::AndImmediate(AndImmediate {
+            dr: 0,
+            sr1: 0,
+            imm5: 0,
+        }),
+        Instruction::Branch(Branch {
+            nzp: CondFlag::all(),
+            pc_offset9: 0xFFFF,
+        }),
+    ]);
+    for &byte in b"HALT\n\0" {
+        loops.extend_from_slice(&[0, byte]);
+    }
+
+    let halted = run_test_case(&halts, b"", 100).unwrap();
+    let looped = run_test_case(&loops, b"", 100).unwrap();
+    assert_eq!(halted.output, looped.output);
+    assert_eq!(halted.registers, looped.registers);
+    assert!(!behaviorally_equivalent(&halts, &loops, b"", 100));
+}
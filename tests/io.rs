@@ -0,0 +1,112 @@
+use std::io::Cursor;
+
+use lilc3::{
+    instruction::{Instruction, Trap, TrapCode},
+    io::{BufferedIo, Io, ReadWriteIo, TeeIo},
+    StopReason, LC3, MAX_MEMORY_SIZE, PROGRAM_START,
+};
+
+fn trap(vect8: TrapCode) -> u16 {
+    u16::from_be(Instruction::Trap(Trap { vect8 }).encode())
+}
+
+/// Output that only shows up in `flushed` when it's flushed
+#[derive(Default)]
+struct FlushTrackingIo {
+    pending: Vec<u8>,
+    flushed: Vec<u8>,
+    flushes: usize,
+}
+
+impl Io for FlushTrackingIo {
+    fn read_byte(&mut self) -> Option<u8> {
+        None
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.pending.extend_from_slice(bytes);
+    }
+
+    fn flush(&mut self) {
+        self.flushed.append(&mut self.pending);
+        self.flushes += 1;
+    }
+}
+
+#[test]
+fn line_buffered_output_flushed_by_halt() {
+    let mut program = vec![trap(TrapCode::Out); 5];
+    program.push(trap(TrapCode::Halt));
+    let mut machine =
+        LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(FlushTrackingIo::default());
+    machine.load_words_at(PROGRAM_START, &program).unwrap();
+    machine.line_buffered_output = true;
+    machine.registers[0] = b'a' as u16;
+
+    assert_eq!(machine.run(), StopReason::Halted { code: 0 });
+    assert_eq!(machine.io.flushed, b"aaaaaHALT\n");
+    assert_eq!(machine.io.flushes, 1);
+}
+
+#[test]
+fn unbuffered_output_flushed_per_trap() {
+    let program = [trap(TrapCode::Out), trap(TrapCode::Out)];
+    let mut machine =
+        LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(FlushTrackingIo::default());
+    machine.load_words_at(PROGRAM_START, &program).unwrap();
+    machine.registers[0] = b'a' as u16;
+    machine.step_n(2);
+
+    assert_eq!(machine.io.flushed, b"aa");
+    assert_eq!(machine.io.flushes, 2);
+}
+
+#[test]
+fn tee_copies_output() {
+    let program = [
+        trap(TrapCode::GetC),
+        trap(TrapCode::Out),
+        trap(TrapCode::Halt),
+    ];
+    let io = TeeIo::new(BufferedIo::new(b"x"));
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(io);
+    machine.load_words_at(PROGRAM_START, &program).unwrap();
+    machine.run();
+
+    assert_eq!(machine.io.captured, b"xHALT\n");
+    assert_eq!(machine.io.inner.output, b"xHALT\n");
+    assert!(machine.io.inner.input.is_empty());
+}
+
+#[test]
+fn read_write_echo() {
+    let program = [
+        trap(TrapCode::GetC),
+        trap(TrapCode::Out),
+        trap(TrapCode::GetC),
+        trap(TrapCode::Out),
+        trap(TrapCode::GetC),
+    ];
+    let io = ReadWriteIo::new(Cursor::new(b"ok".to_vec()), Vec::new());
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(io);
+    machine.load_words_at(PROGRAM_START, &program).unwrap();
+
+    assert_eq!(machine.run(), StopReason::InputExhausted);
+    assert_eq!(machine.io.writer, b"ok");
+}
+
+#[test]
+fn output_map_drops_bell() {
+    let program = [trap(TrapCode::Puts), trap(TrapCode::Halt)];
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(BufferedIo::default());
+    machine.load_words_at(PROGRAM_START, &program).unwrap();
+    machine
+        .load_words_at(0x4000, &[b'h' as u16, 0x07, b'i' as u16, b'\t' as u16, 0])
+        .unwrap();
+    machine.registers[0] = 0x4000;
+    machine.output_map.insert(0x07, None);
+    machine.output_map.insert(b'\t', Some(b' '));
+    machine.run();
+
+    assert_eq!(machine.io.output, b"hi HALT\n");
+}
@@ -0,0 +1,87 @@
+use std::time::{Duration, Instant};
+
+use lilc3::{
+    instruction::{AddImmediate, Branch, Instruction, JumpSubRoutineOffset, StoreBaseOffset},
+    io::BufferedIo,
+    CondFlag, StopReason, LC3, MAX_MEMORY_SIZE, PROGRAM_START,
+};
+
+fn machine(program: &[Instruction]) -> LC3<BufferedIo> {
+    let words: Vec<u16> = program.iter().map(|i| u16::from_be(i.encode())).collect();
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(BufferedIo::default());
+    machine.load_words_at(PROGRAM_START, &words).unwrap();
+    machine
+}
+
+/// Stores R0 at the address in R1 and increments R1 forever
+fn store_loop() -> LC3<BufferedIo> {
+    let mut machine = machine(&[
+        Instruction::StoreBaseOffset(StoreBaseOffset {
+            sr: 0,
+            base_r: 1,
+            pc_offset6: 0,
+        }),
+        Instruction::AddImmediate(AddImmediate {
+            dr: 1,
+            sr1: 1,
+            imm5: 1,
+        }),
+        Instruction::Branch(Branch {
+            nzp: CondFlag::all(),
+            pc_offset9: 0xFFFD,
+        }),
+    ]);
+    machine.registers[0] = 0xBEEF;
+    machine.registers[1] = 0x4000;
+    machine
+}
+
+#[test]
+fn write_limit_stops_store_loop() {
+    let mut machine = store_loop();
+    machine.write_limit = Some(5);
+
+    assert_eq!(machine.run(), StopReason::WriteLimitExceeded);
+    assert_eq!(machine.memory_writes, 5);
+    assert_eq!(machine.memory[0x4004], 0xBEEF);
+    assert_eq!(machine.memory[0x4005], 0);
+}
+
+#[test]
+fn writes_counted_without_limit() {
+    let mut machine = store_loop();
+
+    assert_eq!(machine.step_n(30), None);
+    assert_eq!(machine.memory_writes, 10);
+}
+
+#[test]
+fn recursion_limit_stops_unbounded_recursion() {
+    // A subroutine that calls itself before doing anything else
+    let mut machine = machine(&[Instruction::JumpSubRoutineOffset(JumpSubRoutineOffset {
+        pc_offset11: 0xFFFF,
+    })]);
+    machine.max_call_depth = Some(8);
+
+    assert_eq!(machine.run(), StopReason::RecursionLimit);
+    assert_eq!(machine.call_depth, 8);
+    assert_eq!(machine.pc, PROGRAM_START + 1);
+    assert_eq!(machine.registers[7], PROGRAM_START + 1);
+}
+
+#[test]
+fn timeout_stops_tight_loop() {
+    let mut machine = machine(&[Instruction::Branch(Branch {
+        nzp: CondFlag::all(),
+        pc_offset9: 0xFFFF,
+    })]);
+    let timeout = Duration::from_millis(50);
+
+    let started = Instant::now();
+    assert_eq!(machine.run_with_timeout(timeout), StopReason::Timeout);
+    let elapsed = started.elapsed();
+
+    assert!(elapsed >= timeout);
+    assert!(elapsed < Duration::from_secs(5));
+    assert_eq!(machine.pc, PROGRAM_START);
+}
@@ -0,0 +1,113 @@
+use std::cell::RefCell;
+
+use lilc3::{
+    instruction::{Instruction, Load, Store},
+    io::BufferedIo,
+    memory::MemoryBacking,
+    Memory, MemoryLocationSize, StopReason, LC3, MAX_MEMORY_SIZE, PROGRAM_START,
+};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+enum Access {
+    Read(MemoryLocationSize),
+    Write(MemoryLocationSize, MemoryLocationSize),
+}
+
+/// Memory that records every access made to it
+struct LoggingMemory {
+    memory: Box<Memory>,
+    log: RefCell<Vec<Access>>,
+}
+
+impl MemoryBacking for LoggingMemory {
+    fn read(&self, address: MemoryLocationSize) -> MemoryLocationSize {
+        self.log.borrow_mut().push(Access::Read(address));
+        self.memory.read(address)
+    }
+
+    fn write(&mut self, address: MemoryLocationSize, value: MemoryLocationSize) {
+        self.log.borrow_mut().push(Access::Write(address, value));
+        self.memory.write(address, value)
+    }
+}
+
+#[test]
+fn custom_backing() {
+    let mut memory = Box::new([0; MAX_MEMORY_SIZE]);
+    let program = [
+        Instruction::Load(Load {
+            dr: 0,
+            pc_offset9: 2,
+        }),
+        Instruction::Store(Store {
+            sr: 0,
+            pc_offset9: 2,
+        }),
+    ];
+    for (i, instr) in program.iter().enumerate() {
+        memory[PROGRAM_START as usize + i] = u16::from_be(instr.encode());
+    }
+    memory[PROGRAM_START as usize + 3] = 17;
+
+    let memory = LoggingMemory {
+        memory,
+        log: RefCell::new(Vec::new()),
+    };
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE])
+        .with_io(BufferedIo::default())
+        .with_memory(memory);
+    machine.step_n(2);
+
+    assert_eq!(
+        *machine.memory.log.borrow(),
+        vec![
+            Access::Read(PROGRAM_START),
+            Access::Read(PROGRAM_START + 3),
+            Access::Read(PROGRAM_START + 1),
+            Access::Write(PROGRAM_START + 4, 17),
+        ]
+    );
+}
+
+fn uninit_machine(program: &[Instruction]) -> LC3<BufferedIo> {
+    let words: Vec<u16> = program.iter().map(|i| u16::from_be(i.encode())).collect();
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(BufferedIo::default());
+    machine.load_words_at(PROGRAM_START, &words).unwrap();
+    machine.trap_uninit_reads = true;
+    machine
+}
+
+#[test]
+fn uninit_read_stops() {
+    let mut machine = uninit_machine(&[Instruction::Load(Load {
+        dr: 0,
+        pc_offset9: 5,
+    })]);
+    machine.registers[0] = 3;
+
+    assert_eq!(machine.run(), StopReason::UninitRead(PROGRAM_START + 6));
+    assert_eq!(machine.registers[0], 3);
+}
+
+#[test]
+fn read_after_write_is_initialized() {
+    let mut machine = uninit_machine(&[
+        Instruction::Store(Store {
+            sr: 1,
+            pc_offset9: 5,
+        }),
+        Instruction::Load(Load {
+            dr: 0,
+            pc_offset9: 4,
+        }),
+        Instruction::Load(Load {
+            dr: 2,
+            pc_offset9: 0xFFFD, // the store
+        }),
+    ]);
+    machine.registers[1] = 9;
+
+    assert_eq!(machine.step_n(3), None);
+    assert_eq!(machine.registers[0], 9);
+    assert_eq!(machine.registers[2], machine.memory[PROGRAM_START as usize]);
+}
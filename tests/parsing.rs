@@ -0,0 +1,75 @@
+use lilc3::instruction::{OpCode, ParseNameError, TrapCode};
+
+#[test]
+fn trap_code_names() {
+    assert_eq!("GETC".parse(), Ok(TrapCode::GetC));
+    assert_eq!("out".parse(), Ok(TrapCode::Out));
+    assert_eq!("PutsP".parse(), Ok(TrapCode::PutsP));
+    assert_eq!("HALT".parse(), Ok(TrapCode::Halt));
+}
+
+#[test]
+fn trap_code_name_round_trip() {
+    let names = [
+        (TrapCode::GetC, "GETC"),
+        (TrapCode::Out, "OUT"),
+        (TrapCode::Puts, "PUTS"),
+        (TrapCode::In, "IN"),
+        (TrapCode::PutsP, "PUTSP"),
+        (TrapCode::Halt, "HALT"),
+        (TrapCode::Gets, "GETS"),
+        (TrapCode::PutN, "PUTN"),
+        (TrapCode::ClrScr, "CLRSCR"),
+        (TrapCode::Flush, "FLUSH"),
+    ];
+
+    for &(code, name) in &names {
+        assert_eq!(code.name(), name);
+        assert_eq!(name.parse(), Ok(code));
+    }
+}
+
+#[test]
+fn trap_code_vectors() {
+    assert_eq!("x25".parse(), Ok(TrapCode::Halt));
+    assert_eq!("X20".parse(), Ok(TrapCode::GetC));
+    assert_eq!("0x22".parse(), Ok(TrapCode::Puts));
+}
+
+#[test]
+fn trap_code_unknown() {
+    assert_eq!(
+        "x30".parse::<TrapCode>(),
+        Err(ParseNameError("x30".to_string()))
+    );
+    assert!("HALTS".parse::<TrapCode>().is_err());
+    assert!("x".parse::<TrapCode>().is_err());
+    assert!("".parse::<TrapCode>().is_err());
+}
+
+#[test]
+fn opcode_mnemonics() {
+    assert_eq!("ADD".parse(), Ok(OpCode::Add));
+    assert_eq!("ldr".parse(), Ok(OpCode::LoadBaseOffset));
+    assert_eq!("RET".parse(), Ok(OpCode::Jump));
+    assert_eq!("JSRR".parse(), Ok(OpCode::JumpSubRoutine));
+    assert_eq!("TRAP".parse(), Ok(OpCode::Trap));
+}
+
+#[test]
+fn opcode_branches() {
+    assert_eq!("BR".parse(), Ok(OpCode::Branch));
+    assert_eq!("BRnz".parse(), Ok(OpCode::Branch));
+    assert_eq!("BRNZP".parse(), Ok(OpCode::Branch));
+    assert!("BRzn".parse::<OpCode>().is_err());
+    assert!("BRnn".parse::<OpCode>().is_err());
+}
+
+#[test]
+fn opcode_unknown() {
+    assert_eq!(
+        "MUL".parse::<OpCode>(),
+        Err(ParseNameError("MUL".to_string()))
+    );
+    assert!("".parse::<OpCode>().is_err());
+}
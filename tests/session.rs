@@ -0,0 +1,57 @@
+use lilc3::{
+    instruction::{Instruction, Trap, TrapCode},
+    io::{BufferedIo, RecordingIo},
+    session::Session,
+    StopReason, LC3, PROGRAM_START,
+};
+
+fn object_file(program: &[Instruction]) -> Vec<u8> {
+    let mut bytes = PROGRAM_START.to_be_bytes().to_vec();
+    for instr in program {
+        bytes.extend_from_slice(&u16::from_be(instr.encode()).to_be_bytes());
+    }
+    bytes
+}
+
+fn trap(vect8: TrapCode) -> Instruction {
+    Instruction::Trap(Trap { vect8 })
+}
+
+#[test]
+fn record_then_replay() {
+    let program = object_file(&[
+        trap(TrapCode::In),
+        trap(TrapCode::Out),
+        trap(TrapCode::GetC),
+        trap(TrapCode::Out),
+        trap(TrapCode::Halt),
+    ]);
+
+    let io = RecordingIo::new(BufferedIo::new(b"hi and more"));
+    let mut machine = LC3::new(&program).with_io(io);
+    let stop_reason = machine.run();
+    let session = Session {
+        program,
+        input: machine.io.recorded,
+    };
+    assert_eq!(session.input, b"hi");
+
+    let session = Session::from_bytes(&session.to_bytes()).unwrap();
+    let (replayed_reason, replayed_output) = LC3::replay(&session).unwrap();
+
+    assert_eq!(replayed_reason, stop_reason);
+    assert_eq!(replayed_reason, StopReason::Halted { code: 0 });
+    assert_eq!(replayed_output, machine.io.inner.output);
+}
+
+#[test]
+fn truncated_session() {
+    let session = Session {
+        program: vec![0x30, 0x00, 0xF0, 0x25],
+        input: Vec::new(),
+    };
+    let bytes = session.to_bytes();
+
+    assert_eq!(Session::from_bytes(&bytes[..bytes.len() - 1]), None);
+    assert_eq!(Session::from_bytes(&bytes[..2]), None);
+}
@@ -0,0 +1,114 @@
+use lilc3::{
+    instruction::{
+        AddImmediate, Branch, Instruction, Load, LoadIndirect, StoreBaseOffset, Trap, TrapCode,
+    },
+    io::BufferedIo,
+    CondFlag, LC3, MAX_MEMORY_SIZE, PROGRAM_START,
+};
+
+fn machine(program: &[Instruction]) -> LC3<BufferedIo> {
+    let words: Vec<u16> = program.iter().map(|i| u16::from_be(i.encode())).collect();
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(BufferedIo::default());
+    machine.load_words_at(PROGRAM_START, &words).unwrap();
+    machine
+}
+
+fn add(imm5: u16) -> Instruction {
+    Instruction::AddImmediate(AddImmediate {
+        dr: 0,
+        sr1: 0,
+        imm5,
+    })
+}
+
+/// Counts R0 down from 3, branching back while it's positive
+fn countdown() -> LC3<BufferedIo> {
+    machine(&[
+        add(3),
+        add(0x1F), // negative one
+        Instruction::Branch(Branch {
+            nzp: CondFlag::POSITIVE,
+            pc_offset9: 0xFFFE, // back to the decrement
+        }),
+        Instruction::Trap(Trap {
+            vect8: TrapCode::Halt,
+        }),
+    ])
+}
+
+#[test]
+fn loop_branch_counts() {
+    let mut machine = countdown();
+    machine.collect_branch_stats = true;
+    machine.run();
+
+    let stats = machine.branch_stats();
+    assert_eq!(stats.taken, 2);
+    assert_eq!(stats.not_taken, 1);
+    assert_eq!(stats.hottest(5), vec![(PROGRAM_START + 2, 3)]);
+}
+
+#[test]
+fn stats_disabled() {
+    let mut machine = countdown();
+    machine.run();
+
+    assert_eq!(machine.branch_stats(), Default::default());
+}
+
+#[test]
+fn indirect_load_stalls_twice_as_long() {
+    let mut machine = machine(&[
+        Instruction::Load(Load {
+            dr: 0,
+            pc_offset9: 0,
+        }),
+        Instruction::LoadIndirect(LoadIndirect {
+            dr: 0,
+            pc_offset9: 0,
+        }),
+    ]);
+    machine.memory_stall_cycles = 3;
+
+    machine.step().unwrap();
+    assert_eq!(machine.stall_cycles, 3);
+    assert_eq!(machine.cycle_cost(), 4);
+
+    machine.step().unwrap();
+    assert_eq!(machine.stall_cycles, 9);
+    assert_eq!(machine.cycle_cost(), 11);
+}
+
+#[test]
+fn stack_usage_tracks_lowest_r6() {
+    let adjust_sp = |imm5| {
+        Instruction::AddImmediate(AddImmediate {
+            dr: 6,
+            sr1: 6,
+            imm5,
+        })
+    };
+    let push = Instruction::StoreBaseOffset(StoreBaseOffset {
+        sr: 0,
+        base_r: 6,
+        pc_offset6: 0,
+    });
+    let mut machine = machine(&[
+        adjust_sp(0xFFFF),
+        push,
+        adjust_sp(0xFFFF),
+        push,
+        adjust_sp(0xFFFF),
+        push,
+        adjust_sp(3),
+        Instruction::Trap(Trap {
+            vect8: TrapCode::Halt,
+        }),
+    ]);
+    machine.registers[6] = 0x4000;
+    machine.track_stack_usage = true;
+    machine.run();
+
+    assert_eq!(machine.registers[6], 0x4000);
+    assert_eq!(machine.max_stack_usage(), Some(0x3FFD));
+}
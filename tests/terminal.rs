@@ -0,0 +1,75 @@
+#![cfg(feature = "raw_terminal")]
+
+use std::{
+    io,
+    panic::{self, AssertUnwindSafe},
+    sync::{Arc, Mutex},
+};
+
+use lilc3::{
+    instruction::{Instruction, Trap, TrapCode},
+    io::BufferedIo,
+    terminal::Terminal,
+    StopReason, LC3, MAX_MEMORY_SIZE, PROGRAM_START,
+};
+
+/// Records whether it's in raw mode instead of touching a real terminal
+#[derive(Clone, Default)]
+struct FakeTerminal {
+    raw: Arc<Mutex<bool>>,
+}
+
+impl Terminal for FakeTerminal {
+    fn enter_raw_mode(&mut self) -> io::Result<()> {
+        *self.raw.lock().unwrap() = true;
+        Ok(())
+    }
+
+    fn restore(&mut self) -> io::Result<()> {
+        *self.raw.lock().unwrap() = false;
+        Ok(())
+    }
+}
+
+fn getc_then_halt() -> LC3<BufferedIo> {
+    let program: Vec<u16> = [TrapCode::GetC, TrapCode::Halt]
+        .iter()
+        .map(|&vect8| u16::from_be(Instruction::Trap(Trap { vect8 }).encode()))
+        .collect();
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(BufferedIo::new(b"k"));
+    machine.load_words_at(PROGRAM_START, &program).unwrap();
+    machine
+}
+
+#[test]
+fn raw_mode_for_duration_of_run() {
+    let terminal = FakeTerminal::default();
+    let mut machine = getc_then_halt();
+    let raw = terminal.raw.clone();
+    machine.set_trap_handler(
+        TrapCode::GetC,
+        Box::new(move |machine| {
+            assert!(*raw.lock().unwrap());
+            machine.registers[0] = b'k' as u16;
+        }),
+    );
+
+    let stop_reason = machine.run_with_raw_terminal(terminal.clone()).unwrap();
+
+    assert_eq!(stop_reason, StopReason::Halted { code: 0 });
+    assert!(!*terminal.raw.lock().unwrap());
+}
+
+#[test]
+fn raw_mode_restored_after_panic() {
+    let terminal = FakeTerminal::default();
+    let mut machine = getc_then_halt();
+    machine.set_trap_handler(TrapCode::GetC, Box::new(|_| panic!("trap failed")));
+
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        machine.run_with_raw_terminal(terminal.clone())
+    }));
+
+    assert!(result.is_err());
+    assert!(!*terminal.raw.lock().unwrap());
+}
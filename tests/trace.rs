@@ -0,0 +1,137 @@
+use std::{
+    io::{self, Write},
+    sync::{Arc, Mutex},
+};
+
+use lilc3::{
+    instruction::{AddImmediate, Instruction, Trap, TrapCode},
+    io::BufferedIo,
+    trace::{TraceEntry, TraceMismatch},
+    LC3, MAX_MEMORY_SIZE, PROGRAM_START,
+};
+
+fn machine(program: &[Instruction]) -> LC3<BufferedIo> {
+    let words: Vec<u16> = program.iter().map(|i| u16::from_be(i.encode())).collect();
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(BufferedIo::default());
+    machine.load_words_at(PROGRAM_START, &words).unwrap();
+    machine
+}
+
+fn golden(program: &[Instruction]) -> Vec<TraceEntry> {
+    program
+        .iter()
+        .enumerate()
+        .map(|(i, instr)| TraceEntry {
+            pc: PROGRAM_START + i as u16,
+            instruction: u16::from_be(instr.encode()),
+        })
+        .collect()
+}
+
+fn add(imm5: u16) -> Instruction {
+    Instruction::AddImmediate(AddImmediate {
+        dr: 1,
+        sr1: 1,
+        imm5,
+    })
+}
+
+fn halt() -> Instruction {
+    Instruction::Trap(Trap {
+        vect8: TrapCode::Halt,
+    })
+}
+
+#[test]
+fn matches_golden() {
+    let program = [add(1), add(2), halt()];
+
+    let mut machine = machine(&program);
+
+    assert_eq!(machine.run_with_golden(&golden(&program)), Ok(()));
+    assert_eq!(machine.registers[1], 3);
+}
+
+#[test]
+fn diverges_from_golden() {
+    let program = [add(1), add(2), halt()];
+    let modified = [add(1), add(3), halt()];
+
+    let mut machine = machine(&modified);
+
+    let expected = golden(&program)[1];
+    let actual = golden(&modified)[1];
+    assert_eq!(
+        machine.run_with_golden(&golden(&program)),
+        Err(TraceMismatch {
+            step: 1,
+            expected,
+            actual: Some(actual),
+        })
+    );
+    assert_eq!(machine.pc, PROGRAM_START + 1);
+}
+
+#[test]
+fn stops_before_golden_ends() {
+    let program = [add(1), halt(), add(2)];
+
+    let mut machine = machine(&program[..2]);
+
+    assert_eq!(
+        machine.run_with_golden(&golden(&program)),
+        Err(TraceMismatch {
+            step: 2,
+            expected: golden(&program)[2],
+            actual: None,
+        })
+    );
+}
+
+/// A buffer that can still be read after the machine takes ownership of a handle to it
+#[derive(Clone, Default)]
+struct SharedBuffer(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[test]
+fn trace_to_writer() {
+    let mut machine = machine(&[add(1), add(2), halt()]);
+    let buffer = SharedBuffer::default();
+    machine.start_trace(buffer.clone());
+    machine.run();
+
+    let trace = String::from_utf8(buffer.0.lock().unwrap().clone()).unwrap();
+    let lines: Vec<_> = trace.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "0 x3000 x1261 ADD R1, R1, #1",
+            "1 x3001 x1262 ADD R1, R1, #2",
+            "2 x3002 xF025 HALT",
+        ]
+    );
+}
+
+#[test]
+fn stop_trace() {
+    let mut machine = machine(&[add(1), add(2), halt()]);
+    let buffer = SharedBuffer::default();
+    machine.start_trace(buffer.clone());
+    machine.step().unwrap();
+    assert!(machine.stop_trace().is_some());
+    machine.run();
+
+    assert_eq!(
+        buffer.0.lock().unwrap().len(),
+        "0 x3000 x1261 ADD R1, R1, #1\n".len()
+    );
+}
@@ -0,0 +1,231 @@
+use lilc3::{
+    instruction::{
+        AddImmediate, AndImmediate, Instruction, Jump, JumpSubRoutineOffset, Load, Store,
+        StoreBaseOffset, Trap, TrapCode,
+    },
+    io::BufferedIo,
+    StopReason, Warning, LC3, MAX_MEMORY_SIZE, PROGRAM_START, REGISTER_COUNT,
+};
+
+fn machine(program: &[Instruction]) -> LC3<BufferedIo> {
+    let words: Vec<u16> = program.iter().map(|i| u16::from_be(i.encode())).collect();
+    let mut machine = LC3::from_start_state([0; MAX_MEMORY_SIZE]).with_io(BufferedIo::default());
+    machine.load_words_at(PROGRAM_START, &words).unwrap();
+    machine
+}
+
+fn object_file(program: &[Instruction]) -> Vec<u8> {
+    let mut bytes = PROGRAM_START.to_be_bytes().to_vec();
+    for instr in program {
+        bytes.extend_from_slice(&u16::from_be(instr.encode()).to_be_bytes());
+    }
+    bytes
+}
+
+fn jsr(pc_offset11: u16) -> Instruction {
+    Instruction::JumpSubRoutineOffset(JumpSubRoutineOffset { pc_offset11 })
+}
+
+fn ret() -> Instruction {
+    Instruction::Jump(Jump { base_r: 7 })
+}
+
+fn halt() -> Instruction {
+    Instruction::Trap(Trap {
+        vect8: TrapCode::Halt,
+    })
+}
+
+#[test]
+fn nested_subroutine_clobbers_return_address() {
+    let mut machine = machine(&[
+        jsr(1), // call outer
+        halt(),
+        jsr(1), // outer: call inner without saving R7
+        ret(),
+        ret(), // inner
+    ]);
+    machine.lint_return_address = true;
+    machine.step_n(3);
+
+    assert_eq!(
+        machine.warnings,
+        vec![Warning::ClobberedReturnAddress(PROGRAM_START + 2)]
+    );
+}
+
+#[test]
+fn sequential_subroutines() {
+    let mut machine = machine(&[jsr(2), jsr(1), halt(), ret()]);
+    machine.lint_return_address = true;
+    machine.run();
+
+    assert_eq!(machine.warnings, vec![]);
+}
+
+#[test]
+fn nested_subroutine_saves_return_address() {
+    // R7 is saved in the word after the program
+    let mut machine = machine(&[
+        jsr(1), // call outer
+        halt(),
+        Instruction::Store(Store {
+            sr: 7,
+            pc_offset9: 4,
+        }),
+        jsr(2), // call inner
+        Instruction::Load(Load {
+            dr: 7,
+            pc_offset9: 2,
+        }),
+        ret(),
+        ret(), // inner
+    ]);
+    machine.lint_return_address = true;
+    machine.run();
+
+    assert_eq!(machine.warnings, vec![]);
+}
+
+#[test]
+fn lint_disabled() {
+    let mut machine = machine(&[jsr(1), halt(), jsr(1), ret(), ret()]);
+    machine.step_n(3);
+
+    assert_eq!(machine.warnings, vec![]);
+}
+
+#[test]
+fn registers_start_at_init() {
+    let machine = LC3::new_with_reg_init(&object_file(&[halt()]), 0xDEAD);
+
+    assert_eq!(machine.registers, [0xDEAD; REGISTER_COUNT]);
+}
+
+#[test]
+fn uninit_register_read() {
+    let program = object_file(&[
+        Instruction::AddImmediate(AddImmediate {
+            dr: 1,
+            sr1: 2,
+            imm5: 1,
+        }),
+        Instruction::AddImmediate(AddImmediate {
+            dr: 3,
+            sr1: 1,
+            imm5: 0,
+        }),
+        halt(),
+    ]);
+    let mut machine = LC3::new_with_reg_init(&program, 0xDEAD).with_io(BufferedIo::default());
+    machine.lint_uninit_registers = true;
+    machine.run();
+
+    assert_eq!(
+        machine.warnings,
+        vec![Warning::UninitializedRegister(PROGRAM_START, 2)]
+    );
+    assert_eq!(machine.registers[3], 0xDEAE);
+}
+
+#[test]
+fn clearing_with_and_initializes_register() {
+    let mut machine = machine(&[
+        Instruction::AndImmediate(AndImmediate {
+            dr: 0,
+            sr1: 0,
+            imm5: 0,
+        }),
+        Instruction::AddImmediate(AddImmediate {
+            dr: 1,
+            sr1: 0,
+            imm5: 1,
+        }),
+        halt(),
+    ]);
+    machine.lint_uninit_registers = true;
+    machine.run();
+
+    assert_eq!(machine.warnings, vec![]);
+    assert_eq!(machine.registers[1], 1);
+}
+
+#[test]
+fn store_into_trap_vector_table() {
+    let mut machine = machine(&[
+        Instruction::StoreBaseOffset(StoreBaseOffset {
+            sr: 0,
+            base_r: 1,
+            pc_offset6: 0,
+        }),
+        Instruction::StoreBaseOffset(StoreBaseOffset {
+            sr: 0,
+            base_r: 2,
+            pc_offset6: 0,
+        }),
+        halt(),
+    ]);
+    machine.registers[1] = 0x0025; // the HALT vector
+    machine.registers[2] = 0x0200; // just past the vector tables
+    machine.lint_vector_table_writes = true;
+    machine.run();
+
+    assert_eq!(machine.warnings, vec![Warning::VectorTableWrite(0x0025)]);
+}
+
+#[test]
+fn stray_r7_write_stops() {
+    let mut machine = machine(&[
+        jsr(2),
+        halt(),
+        halt(),
+        Instruction::AddImmediate(AddImmediate {
+            dr: 7,
+            sr1: 0,
+            imm5: 0,
+        }),
+        ret(),
+    ]);
+    machine.stop_on_r7_write = true;
+
+    assert_eq!(
+        machine.run(),
+        StopReason::UnexpectedR7Write(PROGRAM_START + 3)
+    );
+    assert_eq!(machine.registers[7], PROGRAM_START + 1);
+}
+
+#[test]
+fn jsr_and_ret_allowed_to_use_r7() {
+    let mut machine = machine(&[jsr(1), halt(), ret()]);
+    machine.stop_on_r7_write = true;
+
+    assert_eq!(machine.run(), StopReason::Halted { code: 0 });
+}
+
+#[test]
+fn host_r7_write_allowed() {
+    let mut machine = machine(&[halt()]);
+    machine.stop_on_r7_write = true;
+    machine.set_register(7, 0x1234);
+
+    assert_eq!(machine.registers[7], 0x1234);
+    assert_eq!(machine.run(), StopReason::Halted { code: 0 });
+}
+
+#[test]
+fn jump_into_vector_table() {
+    let mut machine = machine(&[Instruction::Jump(Jump { base_r: 0 })]);
+    machine.registers[0] = 0x0000;
+    machine.lint_vector_table_execution = true;
+
+    machine.step().unwrap();
+    assert_eq!(machine.warnings, vec![]);
+
+    // Address 0 holds 0, which runs as a branch that's never taken, so execution slides along
+    machine.step_n(3);
+    assert_eq!(
+        machine.warnings,
+        vec![Warning::ExecutingVectorTable(0x0000)]
+    );
+}